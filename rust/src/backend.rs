@@ -0,0 +1,432 @@
+//! Storage backends for named data streams.
+
+use crate::path_utils::get_stream_path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[cfg(windows)]
+use windows::{
+    core::*,
+    Win32::Foundation::*,
+    Win32::Storage::FileSystem::*,
+};
+
+/// Abstraction over the storage used for named streams such as `Zone.Identifier`
+///
+/// Stream names are given without the leading colon and without a `:$DATA` suffix.
+/// Implementations report failures through `std::io::Error` so callers can react to
+/// `NotFound` and `PermissionDenied` the same way regardless of the backend.
+pub trait StreamBackend: fmt::Debug + Send + Sync {
+    /// Read the full contents of a named stream
+    fn read_stream(&self, file_path: &Path, stream: &str) -> io::Result<Vec<u8>>;
+
+    /// Create or replace a named stream with the given contents
+    fn write_stream(&self, file_path: &Path, stream: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Remove a named stream, failing with `NotFound` if it does not exist
+    fn remove_stream(&self, file_path: &Path, stream: &str) -> io::Result<()>;
+
+    /// List the names of all named streams attached to a file
+    fn list_streams(&self, file_path: &Path) -> io::Result<Vec<String>>;
+}
+
+/// Strip the optional leading colon and `:$DATA` suffix from a stream name
+fn normalize_stream_name(stream: &str) -> io::Result<&str> {
+    let name = stream.strip_prefix(':').unwrap_or(stream);
+    let name = match name.len().checked_sub(":$DATA".len()) {
+        Some(split) if name.is_char_boundary(split) && name[split..].eq_ignore_ascii_case(":$DATA") => &name[..split],
+        _ => name,
+    };
+
+    if name.is_empty() || name.chars().any(|c| matches!(c, ':' | '\\' | '/' | '\0')) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid stream name: {}", stream),
+        ));
+    }
+
+    Ok(name)
+}
+
+/// Build the error reported when a named stream does not exist
+fn stream_not_found(file_path: &Path, stream: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Stream not found: {}:{}", file_path.display(), stream),
+    )
+}
+
+/// NTFS alternate data stream backend using `file:stream` paths
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NtfsStreamBackend;
+
+impl StreamBackend for NtfsStreamBackend {
+    fn read_stream(&self, file_path: &Path, stream: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.stream_path(file_path, stream)?)
+    }
+
+    fn write_stream(&self, file_path: &Path, stream: &str, data: &[u8]) -> io::Result<()> {
+        fs::write(self.stream_path(file_path, stream)?, data)
+    }
+
+    fn remove_stream(&self, file_path: &Path, stream: &str) -> io::Result<()> {
+        fs::remove_file(self.stream_path(file_path, stream)?)
+    }
+
+    #[cfg(windows)]
+    fn list_streams(&self, file_path: &Path) -> io::Result<Vec<String>> {
+        let mut streams = Vec::new();
+        let file_wide: Vec<u16> = file_path.as_os_str().to_string_lossy().encode_utf16().chain(Some(0)).collect();
+        let mut data = WIN32_FIND_STREAM_DATA::default();
+
+        unsafe {
+            let handle = match FindFirstStreamW(
+                PCWSTR(file_wide.as_ptr()),
+                FindStreamInfoStandard,
+                &mut data as *mut _ as *mut _,
+                None,
+            ) {
+                Ok(handle) => handle,
+                Err(e) if e.code() == HRESULT::from_win32(ERROR_HANDLE_EOF.0) => return Ok(streams),
+                Err(e) => return Err(io::Error::from_raw_os_error(e.code().0 & 0xFFFF)),
+            };
+
+            loop {
+                let len = data.cStreamName.iter().position(|&c| c == 0).unwrap_or(data.cStreamName.len());
+                let raw_name = String::from_utf16_lossy(&data.cStreamName[..len]);
+
+                // The unnamed default stream is reported as "::$DATA"
+                if let Ok(name) = normalize_stream_name(&raw_name) {
+                    streams.push(name.to_string());
+                }
+
+                if let Err(e) = FindNextStreamW(handle, &mut data as *mut _ as *mut _) {
+                    let _ = FindClose(handle);
+                    if e.code() == HRESULT::from_win32(ERROR_HANDLE_EOF.0) {
+                        break;
+                    }
+                    return Err(io::Error::from_raw_os_error(e.code().0 & 0xFFFF));
+                }
+            }
+        }
+
+        Ok(streams)
+    }
+
+    #[cfg(not(windows))]
+    fn list_streams(&self, _file_path: &Path) -> io::Result<Vec<String>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Enumerating NTFS streams is only supported on Windows",
+        ))
+    }
+}
+
+impl NtfsStreamBackend {
+    fn stream_path(&self, file_path: &Path, stream: &str) -> io::Result<PathBuf> {
+        let stream = normalize_stream_name(stream)?;
+        get_stream_path(file_path, stream)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+    }
+}
+
+/// Samba `vfs_streams_xattr` backend for Linux file servers
+///
+/// Samba stores each named stream as an extended attribute called
+/// `<prefix><stream>:$DATA`, with one trailing NUL byte after the stream contents.
+/// This backend reads and writes that representation directly on the server, so
+/// shares can be processed without going through an SMB client.
+///
+/// The command-line tool only runs on Windows, so this backend is used by
+/// programs that embed the library and set it as `Config::backend`.
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct SambaXattrBackend {
+    /// Attribute prefix, matching the `streams_xattr:prefix` share option
+    pub prefix: String,
+    /// Whether the `:$DATA` suffix is stored, matching `streams_xattr:store_stream_type`
+    pub store_stream_type: bool,
+}
+
+#[cfg(unix)]
+impl Default for SambaXattrBackend {
+    fn default() -> Self {
+        Self {
+            prefix: "user.DosStream.".to_string(),
+            store_stream_type: true,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl SambaXattrBackend {
+    /// Create a backend using Samba's default attribute naming
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the extended attribute name for a stream
+    fn attr_name(&self, stream: &str) -> String {
+        if self.store_stream_type {
+            format!("{}{}:$DATA", self.prefix, stream)
+        } else {
+            format!("{}{}", self.prefix, stream)
+        }
+    }
+
+    /// Extract the stream name from an extended attribute name, if it is one of ours
+    fn stream_name(&self, attr: &str) -> Option<String> {
+        let name = attr.strip_prefix(self.prefix.as_str())?;
+        let name = if self.store_stream_type {
+            name.strip_suffix(":$DATA")?
+        } else {
+            name
+        };
+        normalize_stream_name(name).ok().map(str::to_string)
+    }
+
+    /// Find the stored attribute for a stream, matching names case-insensitively like Windows does
+    fn resolve_attr(&self, file_path: &Path, stream: &str) -> io::Result<Option<String>> {
+        let stream = normalize_stream_name(stream)?;
+        let exact = self.attr_name(stream);
+        if xattr::get(file_path, &exact)?.is_some() {
+            return Ok(Some(exact));
+        }
+
+        Ok(self.list_streams(file_path)?
+            .into_iter()
+            .find(|name| name.eq_ignore_ascii_case(stream))
+            .map(|name| self.attr_name(&name)))
+    }
+}
+
+#[cfg(unix)]
+impl StreamBackend for SambaXattrBackend {
+    fn read_stream(&self, file_path: &Path, stream: &str) -> io::Result<Vec<u8>> {
+        let attr = self.resolve_attr(file_path, stream)?
+            .ok_or_else(|| stream_not_found(file_path, stream))?;
+        let mut data = xattr::get(file_path, &attr)?
+            .ok_or_else(|| stream_not_found(file_path, stream))?;
+
+        if data.last() == Some(&0) {
+            data.pop();
+        }
+        Ok(data)
+    }
+
+    fn write_stream(&self, file_path: &Path, stream: &str, data: &[u8]) -> io::Result<()> {
+        let attr = match self.resolve_attr(file_path, stream)? {
+            Some(attr) => attr,
+            None => self.attr_name(normalize_stream_name(stream)?),
+        };
+
+        let mut value = Vec::with_capacity(data.len() + 1);
+        value.extend_from_slice(data);
+        value.push(0);
+        xattr::set(file_path, attr, &value)
+    }
+
+    fn remove_stream(&self, file_path: &Path, stream: &str) -> io::Result<()> {
+        let attr = self.resolve_attr(file_path, stream)?
+            .ok_or_else(|| stream_not_found(file_path, stream))?;
+        xattr::remove(file_path, attr)
+    }
+
+    fn list_streams(&self, file_path: &Path) -> io::Result<Vec<String>> {
+        Ok(xattr::list(file_path)?
+            .filter_map(|attr| self.stream_name(&attr.to_string_lossy()))
+            .collect())
+    }
+}
+
+/// A single named stream held by the in-memory backend
+#[derive(Debug, Clone)]
+struct MemoryStream {
+    name: String,
+    data: Vec<u8>,
+}
+
+/// In-memory backend that simulates NTFS stream semantics
+///
+/// Stream names are case-insensitive, the `:$DATA` suffix is optional, and
+/// removing or reading a missing stream fails with `NotFound`. Individual paths
+/// can be marked as access denied to exercise the elevation handling.
+#[derive(Debug, Default)]
+pub struct MemoryStreamBackend {
+    files: Mutex<HashMap<PathBuf, BTreeMap<String, MemoryStream>>>,
+    denied: Mutex<HashSet<PathBuf>>,
+}
+
+impl MemoryStreamBackend {
+    /// Create an empty in-memory backend
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every operation on the given file fail with `PermissionDenied`
+    pub fn deny_access(&self, file_path: &Path) {
+        self.denied.lock().unwrap().insert(file_path.to_path_buf());
+    }
+
+    /// Check whether a named stream exists on the given file
+    pub fn has_stream(&self, file_path: &Path, stream: &str) -> bool {
+        self.read_stream(file_path, stream).is_ok()
+    }
+
+    fn check_access(&self, file_path: &Path) -> io::Result<()> {
+        if self.denied.lock().unwrap().contains(file_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Access denied: {}", file_path.display()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl StreamBackend for MemoryStreamBackend {
+    fn read_stream(&self, file_path: &Path, stream: &str) -> io::Result<Vec<u8>> {
+        let name = normalize_stream_name(stream)?;
+        self.check_access(file_path)?;
+
+        self.files.lock().unwrap()
+            .get(file_path)
+            .and_then(|streams| streams.get(&name.to_lowercase()))
+            .map(|s| s.data.clone())
+            .ok_or_else(|| stream_not_found(file_path, name))
+    }
+
+    fn write_stream(&self, file_path: &Path, stream: &str, data: &[u8]) -> io::Result<()> {
+        let name = normalize_stream_name(stream)?;
+        self.check_access(file_path)?;
+
+        let mut files = self.files.lock().unwrap();
+        let streams = files.entry(file_path.to_path_buf()).or_default();
+
+        // Like NTFS, overwriting keeps the casing the stream was created with
+        streams.entry(name.to_lowercase())
+            .and_modify(|s| s.data = data.to_vec())
+            .or_insert_with(|| MemoryStream { name: name.to_string(), data: data.to_vec() });
+        Ok(())
+    }
+
+    fn remove_stream(&self, file_path: &Path, stream: &str) -> io::Result<()> {
+        let name = normalize_stream_name(stream)?;
+        self.check_access(file_path)?;
+
+        self.files.lock().unwrap()
+            .get_mut(file_path)
+            .and_then(|streams| streams.remove(&name.to_lowercase()))
+            .map(|_| ())
+            .ok_or_else(|| stream_not_found(file_path, name))
+    }
+
+    fn list_streams(&self, file_path: &Path) -> io::Result<Vec<String>> {
+        self.check_access(file_path)?;
+
+        Ok(self.files.lock().unwrap()
+            .get(file_path)
+            .map(|streams| streams.values().map(|s| s.name.clone()).collect())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_stream_name() {
+        assert_eq!(normalize_stream_name("Zone.Identifier").unwrap(), "Zone.Identifier");
+        assert_eq!(normalize_stream_name(":Zone.Identifier:$DATA").unwrap(), "Zone.Identifier");
+        assert_eq!(normalize_stream_name("Zone.Identifier:$data").unwrap(), "Zone.Identifier");
+        assert!(normalize_stream_name("::$DATA").is_err());
+        assert!(normalize_stream_name("bad:name").is_err());
+        assert!(normalize_stream_name("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_samba_attr_names() {
+        let backend = SambaXattrBackend::new();
+        assert_eq!(backend.attr_name("Zone.Identifier"), "user.DosStream.Zone.Identifier:$DATA");
+        assert_eq!(
+            backend.stream_name("user.DosStream.Zone.Identifier:$DATA"),
+            Some("Zone.Identifier".to_string())
+        );
+        assert_eq!(backend.stream_name("user.DosAttrib"), None);
+        assert_eq!(backend.stream_name("user.DosStream.Zone.Identifier"), None);
+
+        let untyped = SambaXattrBackend { store_stream_type: false, ..SambaXattrBackend::new() };
+        assert_eq!(untyped.attr_name("Zone.Identifier"), "user.DosStream.Zone.Identifier");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_samba_backend_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let backend = SambaXattrBackend::new();
+
+        // Not every filesystem used for tests supports user extended attributes
+        if xattr::set(file.path(), "user.unblocker-probe", b"").is_err() {
+            return;
+        }
+
+        backend.write_stream(file.path(), "Zone.Identifier", b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        let raw = xattr::get(file.path(), "user.DosStream.Zone.Identifier:$DATA").unwrap().unwrap();
+        assert_eq!(raw.last(), Some(&0));
+
+        assert_eq!(
+            backend.read_stream(file.path(), "zone.identifier").unwrap(),
+            b"[ZoneTransfer]\r\nZoneId=3\r\n"
+        );
+        assert_eq!(backend.list_streams(file.path()).unwrap(), vec!["Zone.Identifier".to_string()]);
+
+        backend.remove_stream(file.path(), "Zone.Identifier").unwrap();
+        let err = backend.remove_stream(file.path(), "Zone.Identifier").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_memory_backend_round_trip() {
+        let backend = MemoryStreamBackend::new();
+        let file = Path::new("/data/file.txt");
+
+        backend.write_stream(file, "Zone.Identifier", b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        assert_eq!(
+            backend.read_stream(file, "zone.identifier:$DATA").unwrap(),
+            b"[ZoneTransfer]\r\nZoneId=3\r\n"
+        );
+        assert_eq!(backend.list_streams(file).unwrap(), vec!["Zone.Identifier".to_string()]);
+
+        backend.remove_stream(file, "ZONE.IDENTIFIER").unwrap();
+        assert!(!backend.has_stream(file, "Zone.Identifier"));
+        assert!(backend.list_streams(file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_backend_missing_stream() {
+        let backend = MemoryStreamBackend::new();
+        let file = Path::new("/data/file.txt");
+
+        let err = backend.remove_stream(file, "Zone.Identifier").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let err = backend.read_stream(file, "Zone.Identifier").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_memory_backend_access_denied() {
+        let backend = MemoryStreamBackend::new();
+        let file = Path::new("/data/locked.txt");
+        backend.write_stream(file, "Zone.Identifier", b"").unwrap();
+        backend.deny_access(file);
+
+        let err = backend.remove_stream(file, "Zone.Identifier").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,98 @@
+//! Cancellation of a run in progress, on request or when its time limit is reached.
+
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Why a run stopped before every file was processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelReason {
+    /// `CancelToken::cancel` was called, e.g. on Ctrl-C
+    Requested,
+    /// The time limit of the run was reached
+    TimeLimit,
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CancelReason::Requested => write!(f, "cancelled"),
+            CancelReason::TimeLimit => write!(f, "time limit reached"),
+        }
+    }
+}
+
+/// Asks a run to stop after the file it is currently processing
+///
+/// Clones share the same state, so a clone can be handed to a signal handler
+/// or another thread and cancelled from there.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    requested: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the run once `limit` has passed from now
+    ///
+    /// Only clones made after this call see the time limit.
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.deadline = Some(Instant::now() + limit);
+    }
+
+    /// Stop the run after the current file
+    pub fn cancel(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Why the run must stop, or `None` while it may continue
+    pub fn reason(&self) -> Option<CancelReason> {
+        if self.requested.load(Ordering::SeqCst) {
+            Some(CancelReason::Requested)
+        } else if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(CancelReason::TimeLimit)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether the run must stop
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    /// Time left before the time limit, if one is set
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_and_time_limit() {
+        let token = CancelToken::new();
+        let handler_copy = token.clone();
+        assert_eq!(token.reason(), None);
+        assert_eq!(token.remaining(), None);
+        handler_copy.cancel();
+        assert_eq!(token.reason(), Some(CancelReason::Requested));
+
+        let mut limited = CancelToken::new();
+        limited.set_time_limit(Duration::from_millis(20));
+        assert!(!limited.is_cancelled());
+        assert!(limited.remaining().unwrap() <= Duration::from_millis(20));
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(limited.reason(), Some(CancelReason::TimeLimit));
+        assert_eq!(limited.remaining(), Some(Duration::ZERO));
+    }
+}
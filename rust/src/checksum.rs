@@ -0,0 +1,271 @@
+//! SHA-256 verification against a hash manifest or checksum sidecar files.
+
+use crate::error::{Result, UnblockerError};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Name of the checksum list looked up next to a download
+pub const SHA256SUMS_NAME: &str = "SHA256SUMS";
+
+/// Extension of a per-file checksum sidecar, e.g. `setup.zip.sha256`
+pub const SIDECAR_EXTENSION: &str = "sha256";
+
+/// Why a file failed checksum verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumVerdict {
+    /// The file is listed by name but its SHA-256 differs
+    Mismatch,
+    /// Neither the manifest nor a sidecar lists the file or its SHA-256
+    Unlisted,
+}
+
+/// One line of a checksum list
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HashEntry {
+    /// Lowercase hex SHA-256
+    hash: String,
+    /// File name the hash belongs to; absent in a sidecar holding only the hash
+    name: Option<String>,
+}
+
+impl HashEntry {
+    fn names(&self, file_name: &str) -> bool {
+        self.name.as_deref().is_some_and(|name| {
+            let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+            name.eq_ignore_ascii_case(file_name)
+        })
+    }
+}
+
+/// SHA-256 hashes in `sha256sum` format
+///
+/// Accepts `<hash>  <name>`, `<hash> *<name>`, BSD-style `SHA256 (<name>) = <hash>`
+/// and lines holding only a hash. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct HashManifest {
+    entries: Vec<HashEntry>,
+    /// Where the manifest was loaded from, for reports and error messages
+    pub path: PathBuf,
+}
+
+impl HashManifest {
+    /// Read and parse a manifest file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => UnblockerError::PathNotFound(path.display().to_string()),
+            _ => UnblockerError::Io(e),
+        })?;
+
+        let mut manifest = Self::parse(&contents)
+            .map_err(|msg| UnblockerError::Config(format!("{}: {}", path.display(), msg)))?;
+        manifest.path = path.to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Parse manifest contents, reporting the first malformed line
+    pub fn parse(contents: &str) -> std::result::Result<Self, String> {
+        let mut entries = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_line(line)
+                .ok_or_else(|| format!("line {}: expected '<sha256>  <file name>'", number + 1))?;
+            entries.push(entry);
+        }
+
+        Ok(Self { entries, path: PathBuf::new() })
+    }
+
+    /// Number of hashes in the manifest
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the manifest lists no hashes at all
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Option<HashEntry> {
+    // BSD style: SHA256 (name) = hash
+    if let Some(rest) = line.strip_prefix("SHA256 (") {
+        let (name, hash) = rest.rsplit_once(") = ")?;
+        return Some(HashEntry { hash: parse_hash(hash)?, name: Some(name.to_string()) });
+    }
+
+    let (hash, name) = match line.split_once(char::is_whitespace) {
+        Some((hash, name)) => (hash, Some(name.trim_start().trim_start_matches('*').to_string())),
+        None => (line, None),
+    };
+    Some(HashEntry { hash: parse_hash(hash)?, name: name.filter(|name| !name.is_empty()) })
+}
+
+fn parse_hash(hash: &str) -> Option<String> {
+    let hash = hash.trim();
+    (hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then(|| hash.to_ascii_lowercase())
+}
+
+/// Compute the lowercase hex SHA-256 of a file's contents
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// `SHA256SUMS` lists already read during a run, by folder
+///
+/// A folder's list is parsed once rather than once for every file in it. The
+/// cache can be shared by worker threads.
+#[derive(Debug, Default)]
+pub struct SidecarCache {
+    lists: Mutex<HashMap<PathBuf, Arc<[HashEntry]>>>,
+}
+
+impl SidecarCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget every list read so far, so that changed lists are read again
+    pub fn clear(&self) {
+        self.lists.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Entries of the `SHA256SUMS` file in `dir`, empty when there is none
+    fn sums(&self, dir: &Path) -> io::Result<Arc<[HashEntry]>> {
+        if let Some(entries) = self.lists.lock().unwrap_or_else(|e| e.into_inner()).get(dir) {
+            return Ok(entries.clone());
+        }
+
+        let entries: Arc<[HashEntry]> = read_sidecar(&dir.join(SHA256SUMS_NAME))?.into();
+        self.lists.lock().unwrap_or_else(|e| e.into_inner()).insert(dir.to_path_buf(), entries.clone());
+        Ok(entries)
+    }
+}
+
+/// Entries of a checksum file, empty when it does not exist
+fn read_sidecar(path: &Path) -> io::Result<Vec<HashEntry>> {
+    match fs::read_to_string(path) {
+        // A malformed sidecar simply vouches for nothing
+        Ok(contents) => Ok(HashManifest::parse(&contents).map(|manifest| manifest.entries).unwrap_or_default()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check whether a file is itself a `SHA256SUMS` list or a `.sha256` sidecar
+fn is_checksum_file(file_name: &str) -> bool {
+    file_name.eq_ignore_ascii_case(SHA256SUMS_NAME)
+        || Path::new(file_name).extension().is_some_and(|ext| ext.eq_ignore_ascii_case(SIDECAR_EXTENSION))
+}
+
+/// Checksum entries that apply to a file from `SHA256SUMS` and `<file>.sha256` next to it
+fn sidecar_entries(path: &Path, file_name: &str, cache: &SidecarCache) -> io::Result<Vec<HashEntry>> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut entries: Vec<HashEntry> = read_sidecar(&dir.join(format!("{}.{}", file_name, SIDECAR_EXTENSION)))?
+        .into_iter()
+        .filter(|entry| entry.name.is_none() || entry.names(file_name))
+        .collect();
+    entries.extend(cache.sums(dir)?.iter().filter(|entry| entry.names(file_name)).cloned());
+
+    Ok(entries)
+}
+
+/// Verify a file against the manifest and, if `sidecars` is given, the checksum files next to it
+///
+/// Any manifest entry with the file's hash vouches for it, whatever its name, so the
+/// manifest works as a hash allowlist. Sidecar entries only count for the file they name,
+/// and the checksum files themselves pass when sidecars are used, as nothing lists them.
+/// Returns `None` when the file is verified.
+pub fn verify(path: &Path, manifest: Option<&HashManifest>, sidecars: Option<&SidecarCache>) -> io::Result<Option<ChecksumVerdict>> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let sidecar = match sidecars {
+        Some(_) if is_checksum_file(&file_name) => return Ok(None),
+        Some(cache) => sidecar_entries(path, &file_name, cache)?,
+        None => Vec::new(),
+    };
+
+    if manifest.is_none_or(HashManifest::is_empty) && sidecar.is_empty() {
+        return Ok(Some(ChecksumVerdict::Unlisted));
+    }
+
+    let hash = sha256_file(path)?;
+    let manifest_entries = manifest.map(|manifest| manifest.entries.as_slice()).unwrap_or_default();
+    if manifest_entries.iter().chain(&sidecar).any(|entry| entry.hash == hash) {
+        return Ok(None);
+    }
+
+    let listed = !sidecar.is_empty() || manifest_entries.iter().any(|entry| entry.names(&file_name));
+    Ok(Some(if listed { ChecksumVerdict::Mismatch } else { ChecksumVerdict::Unlisted }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // SHA-256 of the bytes "hello\n"
+    const HELLO: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    #[test]
+    fn test_manifest_and_sidecars() {
+        let temp_dir = tempdir().unwrap();
+        let listed = temp_dir.path().join("tool.zip");
+        let renamed = temp_dir.path().join("copy.zip");
+        let changed = temp_dir.path().join("changed.zip");
+        fs::write(&listed, "hello\n").unwrap();
+        fs::write(&renamed, "hello\n").unwrap();
+        fs::write(&changed, "tampered\n").unwrap();
+        assert_eq!(sha256_file(&listed).unwrap(), HELLO);
+
+        let manifest = HashManifest::parse(&format!(
+            "# vendor drop\n{}  dist/tool.zip\nSHA256 (changed.zip) = {}\n",
+            HELLO.to_uppercase(),
+            HELLO
+        )).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(verify(&listed, Some(&manifest), None).unwrap(), None);
+        assert_eq!(verify(&renamed, Some(&manifest), None).unwrap(), None);
+        assert_eq!(verify(&changed, Some(&manifest), None).unwrap(), Some(ChecksumVerdict::Mismatch));
+        assert_eq!(verify(&changed, None, None).unwrap(), Some(ChecksumVerdict::Unlisted));
+
+        // Sidecars only vouch for the file they name
+        let sums = temp_dir.path().join(SHA256SUMS_NAME);
+        let own_sidecar = temp_dir.path().join("changed.zip.sha256");
+        fs::write(&sums, format!("{} *TOOL.ZIP\n", HELLO)).unwrap();
+        fs::write(&own_sidecar, format!("{}\n", HELLO)).unwrap();
+        let cache = SidecarCache::new();
+        assert_eq!(verify(&listed, None, Some(&cache)).unwrap(), None);
+        assert_eq!(verify(&renamed, None, Some(&cache)).unwrap(), Some(ChecksumVerdict::Unlisted));
+        assert_eq!(verify(&changed, None, Some(&cache)).unwrap(), Some(ChecksumVerdict::Mismatch));
+        assert_eq!(verify(&sums, None, Some(&cache)).unwrap(), None);
+        assert_eq!(verify(&own_sidecar, None, Some(&cache)).unwrap(), None);
+
+        // The list is read once per folder
+        fs::write(&sums, format!("{} *copy.zip\n", HELLO)).unwrap();
+        assert_eq!(verify(&renamed, None, Some(&cache)).unwrap(), Some(ChecksumVerdict::Unlisted));
+        assert_eq!(verify(&renamed, None, Some(&SidecarCache::new())).unwrap(), None);
+
+        let err = HashManifest::parse("abc  tool.zip\n").unwrap_err();
+        assert!(err.contains("line 1"), "{}", err);
+    }
+}
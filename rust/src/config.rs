@@ -1,19 +1,155 @@
 //! Configuration management for the file unblocker utility.
 
+use crate::backend::{NtfsStreamBackend, StreamBackend};
+use crate::cancel::CancelToken;
+use crate::checksum::HashManifest;
 use crate::error::{Result, UnblockerError};
+use crate::filter::PathFilter;
+use crate::journal::JournalWriter;
+use crate::logging::{LogFormat, LogTime, Logger, Verbosity};
+use crate::observer::Observer;
+use crate::origin::OriginPolicy;
+use crate::path_utils::default_protected_paths;
+use crate::risk::default_risky_extensions;
+use crate::rotation::LogRotation;
+use crate::scan_hook::{split_command, ScanSlots, DEFAULT_SCAN_TIMEOUT};
+use crate::zone::Zone;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How results are written to standard output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable summary sentences
+    #[default]
+    Text,
+    /// A single versioned JSON document once the run has finished
+    Json,
+    /// One JSON event per line while the run is in progress
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = UnblockerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            _ => Err(UnblockerError::Config(format!("Unknown output format: {}", s))),
+        }
+    }
+}
 
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct Config {
-    /// Enable verbose output to console
-    pub verbose: bool,
+    /// How much is written to the console
+    pub verbosity: Verbosity,
     /// Optional path to log file
     pub log_path: Option<String>,
+    /// Layout of console and log file lines
+    pub log_format: LogFormat,
+    /// Time zone of log timestamps
+    pub log_time: LogTime,
+    /// When the log file is rotated and how many old files are kept
+    pub log_rotation: LogRotation,
+    /// Console and log file sink shared by every clone of this configuration
+    pub logger: Arc<Logger>,
     /// Target file or directory path to process
     pub target_path: String,
     /// Whether elevation is required (set during runtime)
     pub requires_elevation: bool,
+    /// Backend used to read, write and remove named streams
+    pub backend: Arc<dyn StreamBackend>,
+    /// Only unblock files from these zones; `None` unblocks every zone
+    pub unblock_zones: Option<Vec<Zone>>,
+    /// Rewrite Zone.Identifier to this zone instead of removing it
+    pub downgrade_to: Option<Zone>,
+    /// Optional undo journal recording every stream before it is changed
+    pub journal_path: Option<String>,
+    /// Journal file kept open for the run, shared by every clone of this configuration
+    pub journal: Arc<JournalWriter>,
+    /// Apply every check but leave all streams untouched
+    pub dry_run: bool,
+    /// Number of worker threads for directory traversal; 0 uses every available core
+    pub workers: usize,
+    /// Path prefixes that are never processed
+    pub protected_paths: Vec<String>,
+    /// Only process files matching one of these globs, relative to the target
+    pub include: Vec<String>,
+    /// Skip files and prune directories matching any of these globs
+    pub exclude: Vec<String>,
+    /// Extensions that are kept blocked unless `allow_risky` is set
+    pub risky_extensions: Vec<String>,
+    /// Unblock high-risk file types such as executables and scripts as well
+    pub allow_risky: bool,
+    /// Only unblock files whose HostUrl or ReferrerUrl matches one of these domains or URL patterns
+    pub trusted_origins: Vec<String>,
+    /// Keep files blocked when their HostUrl or ReferrerUrl matches one of these patterns
+    pub denied_origins: Vec<String>,
+    /// Only unblock files whose SHA-256 is listed in this manifest
+    pub hash_manifest: Option<Arc<HashManifest>>,
+    /// Only unblock files that match a `SHA256SUMS` or `<file>.sha256` sidecar next to them
+    pub verify_checksums: bool,
+    /// Command run on each file before unblocking; only files it exits 0 for are unblocked
+    pub scan_command: Option<String>,
+    /// Time the scanner may take for one file before it is killed
+    pub scan_timeout: Duration,
+    /// Maximum number of scanner processes running at once
+    pub scan_concurrency: usize,
+    /// Scanner slots shared by every clone of this configuration
+    pub scan_slots: Arc<ScanSlots>,
+    /// Format of the results written to standard output
+    pub output_format: OutputFormat,
+    /// Keep a per-file record of every outcome, in traversal order
+    pub collect_records: bool,
+    /// Receives progress and messages in place of the console
+    pub observer: Option<Arc<dyn Observer>>,
+    /// Stops the run after the current file when cancelled or out of time
+    pub cancel: CancelToken,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            verbosity: Verbosity::Normal,
+            log_path: None,
+            log_format: LogFormat::Text,
+            log_time: LogTime::Utc,
+            log_rotation: LogRotation::default(),
+            logger: Arc::new(Logger::default()),
+            target_path: String::new(),
+            requires_elevation: false,
+            backend: Arc::new(NtfsStreamBackend),
+            unblock_zones: None,
+            downgrade_to: None,
+            journal_path: None,
+            journal: Arc::new(JournalWriter::new()),
+            dry_run: false,
+            workers: 1,
+            output_format: OutputFormat::Text,
+            collect_records: false,
+            observer: None,
+            cancel: CancelToken::default(),
+            risky_extensions: default_risky_extensions(),
+            allow_risky: false,
+            protected_paths: default_protected_paths(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            trusted_origins: Vec::new(),
+            denied_origins: Vec::new(),
+            hash_manifest: None,
+            verify_checksums: false,
+            scan_command: None,
+            scan_timeout: DEFAULT_SCAN_TIMEOUT,
+            scan_concurrency: 1,
+            scan_slots: Arc::new(ScanSlots::default()),
+        }
+    }
 }
 
 impl Config {
@@ -24,10 +160,10 @@ impl Config {
         target_path: String,
     ) -> Result<Self> {
         let config = Self {
-            verbose,
+            verbosity: if verbose { Verbosity::Verbose } else { Verbosity::Normal },
             log_path,
             target_path,
-            requires_elevation: false,
+            ..Self::default()
         };
         
         config.validate()?;
@@ -35,6 +171,8 @@ impl Config {
     }
     
     /// Validate the configuration
+    ///
+    /// Configuration errors name the offending setting as it is spelled in the config file.
     pub fn validate(&self) -> Result<()> {
         // Validate target path exists
         if !Path::new(&self.target_path).exists() {
@@ -44,18 +182,85 @@ impl Config {
         // Validate log directory exists if log path is specified
         if let Some(log_path) = &self.log_path {
             if let Some(parent) = Path::new(log_path).parent() {
-                if !parent.exists() {
+                if !parent.as_os_str().is_empty() && !parent.exists() {
                     return Err(UnblockerError::Config(format!(
-                        "Log directory does not exist: {}",
+                        "log: directory does not exist: {}",
                         parent.display()
                     )));
                 }
             }
         }
         
+        // Validate journal directory exists if a journal is specified
+        if let Some(journal_path) = &self.journal_path {
+            if let Some(parent) = Path::new(journal_path).parent() {
+                if !parent.as_os_str().is_empty() && !parent.exists() {
+                    return Err(UnblockerError::Config(format!(
+                        "journal: directory does not exist: {}",
+                        parent.display()
+                    )));
+                }
+            }
+        }
+        
+        // Reject malformed glob patterns before any file is touched
+        PathFilter::new(&self.include, &self.exclude)?;
+        OriginPolicy::new(&self.trusted_origins, &self.denied_origins)?;
+        
+        if let Some(command) = &self.scan_command {
+            split_command(command)?;
+        }
+        if self.scan_timeout.is_zero() {
+            return Err(UnblockerError::Config("scan_timeout: must be at least 1 second".to_string()));
+        }
+        if self.scan_concurrency == 0 {
+            return Err(UnblockerError::Config("scan_concurrency: must be at least 1".to_string()));
+        }
+        
+        if self.unblock_zones.as_ref().is_some_and(|zones| zones.is_empty()) {
+            return Err(UnblockerError::Config(
+                "zones: list is empty, so no file could ever be unblocked".to_string()
+            ));
+        }
+        
+        let lists = [
+            ("risky_extensions", &self.risky_extensions),
+            ("protected_paths", &self.protected_paths),
+        ];
+        for (name, list) in lists {
+            if let Some(index) = list.iter().position(|entry| entry.trim().is_empty()) {
+                return Err(UnblockerError::Config(format!("{}: entry {} is empty", name, index + 1)));
+            }
+        }
+        
+        // Console log lines would corrupt machine-readable output on stdout
+        if self.verbosity >= Verbosity::Verbose && self.output_format != OutputFormat::Text {
+            return Err(UnblockerError::Config(
+                "verbose: console logging cannot be combined with json or ndjson output; use log instead".to_string()
+            ));
+        }
+        
+        // Downgrading only makes sense towards a zone Windows does not prompt for
+        if let Some(zone) = self.downgrade_to {
+            if !matches!(zone, Zone::LocalMachine | Zone::Intranet | Zone::Trusted) {
+                return Err(UnblockerError::Config(format!(
+                    "downgrade_to: cannot downgrade to zone {}; choose Local Machine (0), Intranet (1) or Trusted (2)",
+                    zone
+                )));
+            }
+        }
+        
         Ok(())
     }
     
+    /// Resolve the configured worker count, where 0 means one per available core
+    pub fn effective_workers(&self) -> usize {
+        match self.workers {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        }
+    }
+    
     /// Mark that elevation is required
     pub fn set_requires_elevation(&mut self) {
         self.requires_elevation = true;
@@ -91,4 +296,16 @@ mod tests {
         let config = Config::new(false, Some(log_path), target_path);
         assert!(matches!(config, Err(UnblockerError::Config(_))));
     }
+    
+    #[test]
+    fn test_config_validation_downgrade_zone() {
+        let temp_dir = tempdir().unwrap();
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        
+        config.downgrade_to = Some(Zone::Intranet);
+        assert!(config.validate().is_ok());
+        
+        config.downgrade_to = Some(Zone::Restricted);
+        assert!(matches!(config.validate(), Err(UnblockerError::Config(_))));
+    }
 }
\ No newline at end of file
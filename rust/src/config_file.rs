@@ -0,0 +1,319 @@
+//! TOML configuration file with defaults and named profiles.
+
+use crate::checksum::HashManifest;
+use crate::config::{Config, OutputFormat};
+use crate::error::{Result, UnblockerError};
+use crate::logging::Verbosity;
+use crate::rotation::{parse_duration, parse_size};
+use crate::zone::Zone;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// File name of the portable configuration looked up next to the executable
+pub const PORTABLE_CONFIG_NAME: &str = "unblocker.toml";
+
+/// A zone given either by number or by name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ZoneSetting {
+    Id(u32),
+    Name(String),
+}
+
+impl ZoneSetting {
+    fn to_zone(&self) -> Result<Zone> {
+        match self {
+            ZoneSetting::Id(id) => Ok(Zone::from_id(*id)),
+            ZoneSetting::Name(name) => name.parse(),
+        }
+    }
+}
+
+/// Settings of the `[defaults]` section or of a `[profiles.<name>]` section
+///
+/// Every setting is optional; unset settings keep their previous value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    verbose: Option<bool>,
+    verbosity: Option<String>,
+    log: Option<String>,
+    log_format: Option<String>,
+    log_time: Option<String>,
+    log_max_size: Option<String>,
+    log_max_age: Option<String>,
+    log_keep: Option<usize>,
+    log_compress: Option<bool>,
+    format: Option<String>,
+    zones: Option<Vec<ZoneSetting>>,
+    downgrade_to: Option<ZoneSetting>,
+    journal: Option<String>,
+    dry_run: Option<bool>,
+    threads: Option<usize>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    allow_risky: Option<bool>,
+    risky_extensions: Option<Vec<String>>,
+    protected_paths: Option<Vec<String>>,
+    trusted_origins: Option<Vec<String>>,
+    denied_origins: Option<Vec<String>>,
+    hash_manifest: Option<String>,
+    verify_checksums: Option<bool>,
+    scan_command: Option<String>,
+    scan_timeout: Option<u64>,
+    scan_concurrency: Option<usize>,
+    time_limit: Option<String>,
+}
+
+impl Settings {
+    /// Copy every setting that is present into the configuration
+    ///
+    /// `section` names the section in error messages, e.g. `profiles.cleanup`.
+    /// Relative file paths are resolved against `base`, the folder of the file.
+    fn apply(&self, section: &str, base: &Path, config: &mut Config) -> Result<()> {
+        let invalid = |key: &str, e: UnblockerError| {
+            UnblockerError::Config(format!("{}.{}: {}", section, key, detail(&e)))
+        };
+
+        if let Some(verbose) = self.verbose {
+            config.verbosity = if verbose { Verbosity::Verbose } else { Verbosity::Normal };
+        }
+        if let Some(verbosity) = &self.verbosity {
+            config.verbosity = verbosity.parse().map_err(|e| invalid("verbosity", e))?;
+        }
+        if let Some(log) = &self.log {
+            config.log_path = Some(resolve(base, log));
+        }
+        if let Some(format) = &self.log_format {
+            config.log_format = format.parse().map_err(|e| invalid("log_format", e))?;
+        }
+        if let Some(time) = &self.log_time {
+            config.log_time = time.parse().map_err(|e| invalid("log_time", e))?;
+        }
+        if let Some(size) = &self.log_max_size {
+            config.log_rotation.max_size = Some(parse_size(size).map_err(|e| invalid("log_max_size", e))?);
+        }
+        if let Some(age) = &self.log_max_age {
+            config.log_rotation.max_age = Some(parse_duration(age).map_err(|e| invalid("log_max_age", e))?);
+        }
+        if let Some(keep) = self.log_keep {
+            config.log_rotation.keep = keep;
+        }
+        if let Some(compress) = self.log_compress {
+            config.log_rotation.compress = compress;
+        }
+        if let Some(format) = &self.format {
+            config.output_format = format.parse::<OutputFormat>().map_err(|e| invalid("format", e))?;
+        }
+        if let Some(zones) = &self.zones {
+            let zones = zones.iter()
+                .map(ZoneSetting::to_zone)
+                .collect::<Result<Vec<_>>>()
+                .map_err(|e| invalid("zones", e))?;
+            config.unblock_zones = Some(zones);
+        }
+        if let Some(zone) = &self.downgrade_to {
+            config.downgrade_to = Some(zone.to_zone().map_err(|e| invalid("downgrade_to", e))?);
+        }
+        if let Some(journal) = &self.journal {
+            config.journal_path = Some(resolve(base, journal));
+        }
+        if let Some(dry_run) = self.dry_run {
+            config.dry_run = dry_run;
+        }
+        if let Some(threads) = self.threads {
+            config.workers = threads;
+        }
+        if let Some(include) = &self.include {
+            config.include = include.clone();
+        }
+        if let Some(exclude) = &self.exclude {
+            config.exclude = exclude.clone();
+        }
+        if let Some(allow_risky) = self.allow_risky {
+            config.allow_risky = allow_risky;
+        }
+        if let Some(extensions) = &self.risky_extensions {
+            config.risky_extensions = extensions.clone();
+        }
+        if let Some(paths) = &self.protected_paths {
+            config.protected_paths = paths.clone();
+        }
+        if let Some(origins) = &self.trusted_origins {
+            config.trusted_origins = origins.clone();
+        }
+        if let Some(origins) = &self.denied_origins {
+            config.denied_origins = origins.clone();
+        }
+        if let Some(path) = &self.hash_manifest {
+            let manifest = HashManifest::load(Path::new(&resolve(base, path))).map_err(|e| invalid("hash_manifest", e))?;
+            config.hash_manifest = Some(Arc::new(manifest));
+        }
+        if let Some(verify) = self.verify_checksums {
+            config.verify_checksums = verify;
+        }
+        if let Some(command) = &self.scan_command {
+            config.scan_command = Some(command.clone());
+        }
+        if let Some(seconds) = self.scan_timeout {
+            config.scan_timeout = Duration::from_secs(seconds);
+        }
+        if let Some(concurrency) = self.scan_concurrency {
+            config.scan_concurrency = concurrency;
+        }
+        if let Some(limit) = &self.time_limit {
+            config.cancel.set_time_limit(parse_duration(limit).map_err(|e| invalid("time_limit", e))?);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parsed configuration file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Settings applied to every run
+    #[serde(default)]
+    pub defaults: Settings,
+    /// Named sets of settings selected with `--profile`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Settings>,
+    /// Where the file was loaded from, for error messages
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl ConfigFile {
+    /// Read and parse a configuration file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => UnblockerError::PathNotFound(path.display().to_string()),
+            _ => UnblockerError::Io(e),
+        })?;
+
+        let mut file = Self::parse(&contents)
+            .map_err(|e| UnblockerError::Config(format!("{}: {}", path.display(), detail(&e))))?;
+        file.path = path.to_path_buf();
+        Ok(file)
+    }
+
+    /// Parse configuration file contents
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|e| UnblockerError::Config(e.to_string().trim_end().to_string()))
+    }
+
+    /// Apply the defaults and then the selected profile to a configuration
+    pub fn apply(&self, profile: Option<&str>, config: &mut Config) -> Result<()> {
+        let located = |e: UnblockerError| match e {
+            UnblockerError::Config(msg) => UnblockerError::Config(format!("{}: {}", self.path.display(), msg)),
+            other => other,
+        };
+
+        let base = self.path.parent().unwrap_or(Path::new(""));
+        self.defaults.apply("defaults", base, config).map_err(located)?;
+
+        if let Some(name) = profile {
+            let settings = self.profiles.get(name).ok_or_else(|| {
+                let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                UnblockerError::Config(format!(
+                    "{}: profile '{}' not found (available: {})",
+                    self.path.display(),
+                    name,
+                    if available.is_empty() { "none".to_string() } else { available.join(", ") }
+                ))
+            })?;
+            settings.apply(&format!("profiles.{}", name), base, config).map_err(located)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A path from the file, relative to the folder of the file rather than the working directory
+fn resolve(base: &Path, path: &str) -> String {
+    base.join(path).to_string_lossy().to_string()
+}
+
+/// The message of an error without the prefix naming its variant
+fn detail(e: &UnblockerError) -> String {
+    match e {
+        UnblockerError::Config(msg) | UnblockerError::InvalidZoneIdentifier(msg) => msg.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Portable configuration next to the running executable, if there is one
+pub fn portable_config_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let path = exe.parent()?.join(PORTABLE_CONFIG_NAME);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[defaults]
+log = "unblocker.log"
+log_max_size = "10MB"
+zones = ["internet", 2]
+risky_extensions = ["exe", "js"]
+
+[profiles.downloads-cleanup]
+include = ["*.zip", "*.pdf"]
+downgrade_to = "intranet"
+threads = 4
+"#;
+
+    #[test]
+    fn test_defaults_and_profile() {
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+
+        let mut config = Config::default();
+        file.apply(None, &mut config).unwrap();
+        assert_eq!(config.log_path.as_deref(), Some("unblocker.log"));
+        assert_eq!(config.log_rotation.max_size, Some(10 * 1024 * 1024));
+        assert_eq!(config.unblock_zones, Some(vec![Zone::Internet, Zone::Trusted]));
+        assert_eq!(config.risky_extensions, vec!["exe", "js"]);
+        assert!(config.include.is_empty());
+
+        file.apply(Some("downloads-cleanup"), &mut config).unwrap();
+        assert_eq!(config.include, vec!["*.zip", "*.pdf"]);
+        assert_eq!(config.downgrade_to, Some(Zone::Intranet));
+        assert_eq!(config.workers, 4);
+    }
+
+    #[test]
+    fn test_precise_errors() {
+        let err = ConfigFile::parse("[defaults]\nverbos = true\n").unwrap_err().to_string();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(err.contains("verbos"), "{}", err);
+
+        let file = ConfigFile::parse("[profiles.a]\nzones = [\"moon\"]\n").unwrap();
+        let err = file.apply(Some("a"), &mut Config::default()).unwrap_err().to_string();
+        assert!(err.contains("profiles.a.zones"), "{}", err);
+
+        let err = file.apply(Some("b"), &mut Config::default()).unwrap_err().to_string();
+        assert!(err.contains("profile 'b' not found (available: a)"), "{}", err);
+    }
+
+    #[test]
+    fn test_paths_relative_to_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("unblocker.toml");
+        let absolute = temp_dir.path().join("elsewhere.jsonl").to_string_lossy().to_string();
+        fs::write(&path, format!("[defaults]\nlog = \"logs/unblocker.log\"\njournal = {:?}\n", absolute)).unwrap();
+
+        let mut config = Config::default();
+        ConfigFile::load(&path).unwrap().apply(None, &mut config).unwrap();
+        let log = temp_dir.path().join("logs").join("unblocker.log");
+        assert_eq!(config.log_path, Some(log.to_string_lossy().to_string()));
+        assert_eq!(config.journal_path, Some(absolute));
+    }
+}
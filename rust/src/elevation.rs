@@ -67,18 +67,25 @@ fn escape_argument(arg: &str) -> String {
 }
 
 /// Relaunch the application with administrator privileges
+///
+/// Waits for the elevated instance and returns its exit code.
+pub fn relaunch_as_admin() -> Result<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    relaunch_as_admin_with(&args)
+}
+
+/// Relaunch the application with administrator privileges and the given arguments
+///
+/// Waits for the elevated instance and returns its exit code.
 #[cfg(windows)]
-pub fn relaunch_as_admin() -> Result<()> {
+pub fn relaunch_as_admin_with(args: &[String]) -> Result<i32> {
     use std::env;
-    use std::process;
     
     let current_exe = env::current_exe()
         .map_err(|e| UnblockerError::WindowsApi(format!("Failed to get current executable path: {}", e)))?;
-
-    let args: Vec<String> = env::args().collect();
     
     // Properly escape arguments to prevent injection attacks
-    let escaped_args: Vec<String> = args[1..].iter()
+    let escaped_args: Vec<String> = args.iter()
         .map(|arg| escape_argument(arg))
         .collect();
     let arguments = escaped_args.join(" ");
@@ -86,35 +93,48 @@ pub fn relaunch_as_admin() -> Result<()> {
     log::info!("Relaunching with elevated privileges");
     log::debug!("Executable: {}", current_exe.display());
     log::debug!("Arguments: {}", arguments);
+    // The elevated instance appends to the same log, so this run's lines must be written first
+    log::logger().flush();
 
     unsafe {
         let exe_path: Vec<u16> = current_exe.to_string_lossy().encode_utf16().chain(Some(0)).collect();
         let params: Vec<u16> = arguments.encode_utf16().chain(Some(0)).collect();
         let operation: Vec<u16> = "runas".encode_utf16().chain(Some(0)).collect();
 
-        let result = ShellExecuteW(
-            Some(HWND(std::ptr::null_mut())),
-            PCWSTR(operation.as_ptr()),
-            PCWSTR(exe_path.as_ptr()),
-            PCWSTR(params.as_ptr()),
-            PCWSTR::null(),
-            SHOW_WINDOW_CMD(1), // SW_NORMAL
-        );
-
-        if result.0 as isize <= 32 {
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS,
+            hwnd: HWND(std::ptr::null_mut()),
+            lpVerb: PCWSTR(operation.as_ptr()),
+            lpFile: PCWSTR(exe_path.as_ptr()),
+            lpParameters: PCWSTR(params.as_ptr()),
+            nShow: SW_NORMAL.0,
+            ..Default::default()
+        };
+
+        // Fails when the user declines the UAC prompt
+        ShellExecuteExW(&mut info).map_err(|_| UnblockerError::ElevationFailed)?;
+        if info.hProcess.is_invalid() {
             return Err(UnblockerError::ElevationFailed);
         }
+        let _guard = HandleGuard(info.hProcess);
+
+        WaitForSingleObject(info.hProcess, INFINITE);
+
+        let mut exit_code = 0u32;
+        GetExitCodeProcess(info.hProcess, &mut exit_code)
+            .map_err(|e| UnblockerError::WindowsApi(format!("Failed to get elevated exit code: {:?}", e)))?;
+
+        Ok(exit_code as i32)
     }
-    
-    process::exit(0);
 }
 
 #[cfg(not(windows))]
-pub fn relaunch_as_admin() -> Result<()> {
+pub fn relaunch_as_admin_with(_args: &[String]) -> Result<i32> {
     Err(UnblockerError::WindowsApi("Elevation not supported on this platform".to_string()))
 }
 
-#[cfg(test)]
+#[cfg(all(test, windows))]
 mod tests {
     use super::*;
     
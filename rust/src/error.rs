@@ -28,6 +28,15 @@ pub enum UnblockerError {
     
     #[error("Walkdir error: {0}")]
     WalkDir(#[from] walkdir::Error),
+    
+    #[error("Invalid Zone.Identifier: {0}")]
+    InvalidZoneIdentifier(String),
+    
+    #[error("Journal error: {0}")]
+    Journal(String),
+    
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
 }
 
 /// Result type alias for convenience
@@ -39,6 +48,23 @@ impl UnblockerError {
         matches!(self, UnblockerError::PermissionDenied(_))
     }
     
+    /// Stable machine-readable name of the error variant, used in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            UnblockerError::InvalidPath(_) => "invalid_path",
+            UnblockerError::PathNotFound(_) => "path_not_found",
+            UnblockerError::PermissionDenied(_) => "permission_denied",
+            UnblockerError::Io(_) => "io",
+            UnblockerError::Config(_) => "config",
+            UnblockerError::ElevationFailed => "elevation_failed",
+            UnblockerError::WindowsApi(_) => "windows_api",
+            UnblockerError::WalkDir(_) => "walk_dir",
+            UnblockerError::InvalidZoneIdentifier(_) => "invalid_zone_identifier",
+            UnblockerError::Journal(_) => "journal",
+            UnblockerError::Watch(_) => "watch",
+        }
+    }
+    
     /// Convert to a user-friendly message
     pub fn user_message(&self) -> String {
         match self {
@@ -66,6 +92,15 @@ impl UnblockerError {
             UnblockerError::WalkDir(e) => {
                 format!("Directory traversal error: {}", e)
             }
+            UnblockerError::InvalidZoneIdentifier(msg) => {
+                format!("Unreadable Zone.Identifier data: {}", msg)
+            }
+            UnblockerError::Journal(msg) => {
+                format!("Undo journal error: {}", msg)
+            }
+            UnblockerError::Watch(e) => {
+                format!("Cannot watch folder: {}", e)
+            }
         }
     }
 }
\ No newline at end of file
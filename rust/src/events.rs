@@ -0,0 +1,76 @@
+//! Streaming NDJSON events describing a run while it is in progress.
+
+use crate::config::{Config, OutputFormat};
+use crate::error::{Result, UnblockerError};
+use crate::report::FileReport;
+use crate::unblocker::UnblockStats;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::Write;
+
+/// Something that happened during a run
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Traversal entered a directory
+    Directory { path: String },
+    /// A file was processed; `outcome` tells whether it was unblocked, skipped or failed
+    File(&'a FileReport),
+    /// A file or directory could not be accessed without administrator privileges
+    ElevationNeeded { path: String },
+    /// Totals for the whole run, always the last event
+    Summary { stats: &'a UnblockStats },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    time: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Encode an event as a single JSON line, without the trailing newline
+pub fn to_line(event: &Event) -> Result<String> {
+    let envelope = Envelope { time: Utc::now(), event };
+    serde_json::to_string(&envelope)
+        .map_err(|e| UnblockerError::Config(format!("Cannot serialize event: {}", e)))
+}
+
+/// Write an event to standard output when NDJSON output is selected
+pub fn emit(event: &Event, config: &Config) -> Result<()> {
+    if config.output_format != OutputFormat::Ndjson {
+        return Ok(());
+    }
+
+    let line = to_line(event)?;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(format!("{}\n", line).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unblocker::{FileOutcome, FileRecord};
+
+    #[test]
+    fn test_event_lines() {
+        let line = to_line(&Event::Directory { path: "C:\\Downloads".to_string() }).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["event"], "directory");
+        assert_eq!(json["path"], "C:\\Downloads");
+        assert!(json["time"].is_string());
+
+        let record = FileRecord { path: "a.zip".into(), outcome: Ok(FileOutcome::Unblocked) };
+        let line = to_line(&Event::File(&FileReport::from(&record))).unwrap();
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["event"], "file");
+        assert_eq!(json["outcome"], "unblocked");
+
+        let stats = UnblockStats { files_processed: 4, ..Default::default() };
+        let json: serde_json::Value = serde_json::from_str(&to_line(&Event::Summary { stats: &stats }).unwrap()).unwrap();
+        assert_eq!(json["stats"]["files_processed"], 4);
+    }
+}
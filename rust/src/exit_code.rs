@@ -0,0 +1,82 @@
+//! Documented process exit codes.
+
+use crate::error::UnblockerError;
+use crate::unblocker::UnblockStats;
+
+/// Exit code of the process, for scheduled tasks and scripts
+///
+/// The values are part of the command line interface and must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Every file was processed without failures
+    Success = 0,
+    /// An unexpected error stopped the run
+    Error = 1,
+    /// The command line or configuration is invalid
+    InvalidArguments = 2,
+    /// A target path, path list or journal does not exist
+    TargetNotFound = 3,
+    /// The run finished but some files could not be processed
+    PartialFailure = 4,
+    /// Some files were still access-denied, even after elevation
+    PermissionDenied = 5,
+    /// Elevation was declined at the UAC prompt or could not be started
+    ElevationRefused = 6,
+    /// The run was stopped by Ctrl-C or its time limit before every file was processed
+    Cancelled = 7,
+}
+
+impl ExitCode {
+    /// Numeric value passed to the operating system
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Exit code for a run that finished with the given totals
+    pub fn from_stats(stats: &UnblockStats) -> Self {
+        if stats.cancelled.is_some() {
+            ExitCode::Cancelled
+        } else if stats.permission_errors > 0 {
+            ExitCode::PermissionDenied
+        } else if stats.files_failed > 0 {
+            ExitCode::PartialFailure
+        } else {
+            ExitCode::Success
+        }
+    }
+
+    /// Exit code for a run stopped by an error
+    pub fn from_error(e: &UnblockerError) -> Self {
+        match e {
+            UnblockerError::Config(_) | UnblockerError::InvalidPath(_) => ExitCode::InvalidArguments,
+            UnblockerError::PathNotFound(_) => ExitCode::TargetNotFound,
+            UnblockerError::PermissionDenied(_) => ExitCode::PermissionDenied,
+            UnblockerError::ElevationFailed => ExitCode::ElevationRefused,
+            _ => ExitCode::Error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelReason;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(ExitCode::from_stats(&UnblockStats::default()), ExitCode::Success);
+
+        let failed = UnblockStats { files_failed: 2, ..Default::default() };
+        assert_eq!(ExitCode::from_stats(&failed).code(), 4);
+
+        let denied = UnblockStats { files_failed: 2, permission_errors: 1, ..Default::default() };
+        assert_eq!(ExitCode::from_stats(&denied).code(), 5);
+
+        let stopped = UnblockStats { cancelled: Some(CancelReason::TimeLimit), ..denied };
+        assert_eq!(ExitCode::from_stats(&stopped).code(), 7);
+
+        assert_eq!(ExitCode::from_error(&UnblockerError::PathNotFound("x".to_string())).code(), 3);
+        assert_eq!(ExitCode::from_error(&UnblockerError::Config("x".to_string())).code(), 2);
+        assert_eq!(ExitCode::from_error(&UnblockerError::ElevationFailed).code(), 6);
+    }
+}
@@ -0,0 +1,88 @@
+//! Include and exclude glob filters for directory traversal.
+
+use crate::error::{Result, UnblockerError};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Compiled `--include` / `--exclude` patterns
+///
+/// Patterns match the path relative to the traversal root, ignoring case,
+/// and `*` also matches across directory separators.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    /// Compile include and exclude patterns
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_set(include)?)
+        };
+
+        Ok(Self {
+            include,
+            exclude: build_set(exclude)?,
+        })
+    }
+
+    /// Check whether the filter would let every file through
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_empty()
+    }
+
+    /// Check whether a file should be processed
+    pub fn allows_file(&self, relative: &Path) -> bool {
+        !self.exclude.is_match(relative)
+            && self.include.as_ref().is_none_or(|include| include.is_match(relative))
+    }
+
+    /// Check whether a directory is excluded and must not be walked at all
+    pub fn prunes_dir(&self, relative: &Path) -> bool {
+        !relative.as_os_str().is_empty() && self.exclude.is_match(relative)
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let glob = GlobBuilder::new(&pattern.replace('\\', "/"))
+            .case_insensitive(true)
+            .backslash_escape(false)
+            .build()
+            .map_err(|e| UnblockerError::Config(format!("include/exclude: invalid glob pattern '{}': {}", pattern, e)))?;
+        builder.add(glob);
+    }
+
+    builder.build()
+        .map_err(|e| UnblockerError::Config(format!("include/exclude: invalid glob patterns: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_filter_matching() {
+        let filter = PathFilter::new(&patterns(&["*.EXE", "*.msi"]), &patterns(&["node_modules", "cache\\*.exe"])).unwrap();
+
+        assert!(filter.allows_file(Path::new("setup.exe")));
+        assert!(filter.allows_file(Path::new("sub/dir/Tool.Exe")));
+        assert!(!filter.allows_file(Path::new("readme.txt")));
+        assert!(!filter.allows_file(Path::new("cache/old.exe")));
+        assert!(filter.prunes_dir(Path::new("NODE_MODULES")));
+        assert!(!filter.prunes_dir(Path::new("src")));
+        assert!(!filter.prunes_dir(Path::new("")));
+
+        assert!(PathFilter::new(&[], &[]).unwrap().is_empty());
+        assert!(matches!(PathFilter::new(&patterns(&["[a-"]), &[]), Err(UnblockerError::Config(_))));
+    }
+}
@@ -0,0 +1,381 @@
+//! Undo journal for Zone.Identifier streams removed or rewritten by the unblocker.
+
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use crate::events::{emit, Event};
+use crate::observer::notify_finished;
+use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+use crate::report::FileReport;
+use crate::ui::log_message;
+use crate::unblocker::{prepare_file, stream_error, FileFailure, FileOutcome, FileRecord, SkipReason, UnblockStats};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Entries written before the journal file is synced to disk
+const SYNC_BATCH: usize = 64;
+
+/// One journaled stream, written as a single JSON line before the stream is changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// When the original stream was recorded
+    pub timestamp: DateTime<Utc>,
+    /// File the stream belongs to
+    pub path: PathBuf,
+    /// Name of the stream
+    pub stream: String,
+    /// Original stream contents, hex encoded
+    pub data: String,
+}
+
+impl JournalEntry {
+    /// Create an entry for the Zone.Identifier stream of a file
+    pub fn new(path: &Path, data: &[u8]) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            path: path.to_path_buf(),
+            stream: ZONE_IDENTIFIER_STREAM.to_string(),
+            data: encode_hex(data),
+        }
+    }
+
+    /// Decode the original stream contents
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        decode_hex(&self.data).ok_or_else(|| {
+            UnblockerError::Journal(format!("Corrupt stream data for {}", self.path.display()))
+        })
+    }
+}
+
+/// Selects which journal entries a restore replays
+#[derive(Debug, Clone, Default)]
+pub struct JournalFilter {
+    /// Only restore files at or below this path
+    pub path_prefix: Option<PathBuf>,
+    /// Only restore entries recorded at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only restore entries recorded at or before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl JournalFilter {
+    /// Check whether an entry is selected by the filter
+    pub fn matches(&self, entry: &JournalEntry) -> bool {
+        self.path_prefix.as_ref().is_none_or(|prefix| entry.path.starts_with(prefix))
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+    }
+}
+
+/// Journal file kept open for a whole run
+///
+/// Shared by every clone of a configuration, so worker threads append through
+/// one handle. Each entry is handed to the operating system before its stream
+/// is changed, which survives the process being killed; the file is synced to
+/// disk every few entries and by `sync` at the end of a run.
+#[derive(Debug, Default)]
+pub struct JournalWriter {
+    file: Mutex<Option<OpenJournal>>,
+}
+
+#[derive(Debug)]
+struct OpenJournal {
+    path: String,
+    file: File,
+    unsynced: usize,
+}
+
+impl JournalWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry to the journal at `journal_path`, opening it on first use
+    pub fn append(&self, journal_path: &str, entry: &JournalEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| UnblockerError::Journal(e.to_string()))?;
+        let mut open = self.file.lock().unwrap_or_else(|e| e.into_inner());
+
+        if open.as_ref().is_none_or(|journal| journal.path != journal_path) {
+            if let Some(previous) = open.as_mut() {
+                previous.sync()?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(journal_path)?;
+            *open = Some(OpenJournal { path: journal_path.to_string(), file, unsynced: 0 });
+        }
+
+        let journal = open.as_mut().expect("journal was just opened");
+        // A single write keeps concurrent appends from interleaving within a line
+        journal.file.write_all(format!("{}\n", line).as_bytes())?;
+        journal.unsynced += 1;
+        if journal.unsynced >= SYNC_BATCH {
+            journal.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Sync every appended entry to disk
+    pub fn sync(&self) -> Result<()> {
+        match self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(journal) => journal.sync(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl OpenJournal {
+    fn sync(&mut self) -> Result<()> {
+        if self.unsynced > 0 {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Read every entry from a journal file
+pub fn read_journal(journal_path: &str) -> Result<Vec<JournalEntry>> {
+    let file = File::open(journal_path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => UnblockerError::PathNotFound(journal_path.to_string()),
+        _ => UnblockerError::Io(e),
+    })?;
+
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            UnblockerError::Journal(format!("{} line {}: {}", journal_path, index + 1, e))
+        })?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Put the journaled streams back, replaying entries in the order they were recorded
+pub fn restore_journal(journal_path: &str, filter: &JournalFilter, config: &mut Config) -> Result<UnblockStats> {
+    let mut stats = UnblockStats::default();
+
+    log_message(&format!("Restoring from journal: {}", journal_path), config)?;
+
+    // A stream changed in several runs has several entries; the earliest holds the bytes it was downloaded with
+    let mut seen = HashSet::new();
+    let entries: Vec<JournalEntry> = read_journal(journal_path)?
+        .into_iter()
+        .filter(|entry| filter.matches(entry))
+        .filter(|entry| seen.insert((entry.path.clone(), entry.stream.clone())))
+        .collect();
+    let target = filter.path_prefix.as_deref().unwrap_or(Path::new(journal_path));
+    if let Some(observer) = &config.observer {
+        observer.started(target, observer.wants_totals().then_some(entries.len() as u64));
+    }
+
+    for entry in &entries {
+        if let Some(reason) = config.cancel.reason() {
+            stats.cancelled = Some(reason);
+            break;
+        }
+        stats.files_processed += 1;
+
+        let result = restore_entry(entry, config);
+        match &result {
+            Ok(outcome) => stats.record(outcome),
+            Err(UnblockerError::PermissionDenied(_)) => {
+                emit(&Event::ElevationNeeded { path: entry.path.display().to_string() }, config)?;
+                stats.permission_errors += 1;
+                stats.files_failed += 1;
+            }
+            Err(e) => {
+                log_message(
+                    &format!("Error restoring {}: {}", entry.path.display(), e),
+                    config
+                )?;
+                stats.files_failed += 1;
+            }
+        }
+
+        let record = FileRecord { path: entry.path.clone(), outcome: result.map_err(|e| FileFailure::from(&e)) };
+        if let Some(observer) = &config.observer {
+            observer.file_result(&record);
+        }
+        emit(&Event::File(&FileReport::from(&record)), config)?;
+        if config.collect_records {
+            stats.records.push(record);
+        }
+    }
+
+    log_message(&stats.summary(), config)?;
+    notify_finished(target, &stats, config);
+    Ok(stats)
+}
+
+fn restore_entry(entry: &JournalEntry, config: &mut Config) -> Result<FileOutcome> {
+    let file_path = match prepare_file(&entry.path.to_string_lossy(), config)? {
+        Some(file_path) => file_path,
+        None => return Ok(FileOutcome::Skipped(SkipReason::UnsafePath)),
+    };
+
+    // Writing a stream to a missing file would silently create an empty one
+    if !file_path.is_file() {
+        return Err(UnblockerError::PathNotFound(file_path.display().to_string()));
+    }
+
+    let data = entry.bytes()?;
+    let backend = config.backend.clone();
+
+    match backend.write_stream(&file_path, &entry.stream, &data) {
+        Ok(_) => {
+            log_message(&format!("Restored: {}", file_path.display()), config)?;
+            Ok(FileOutcome::Restored)
+        }
+        Err(e) => Err(stream_error(e, &file_path, "Failed to restore", config)?),
+    }
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::unblocker::process_target;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hex_round_trip() {
+        let data = b"[ZoneTransfer]\r\nZoneId=3\r\n\xff\x00";
+        assert_eq!(decode_hex(&encode_hex(data)).unwrap(), data);
+        assert!(decode_hex("abc").is_none());
+        assert!(decode_hex("zz").is_none());
+    }
+
+    #[test]
+    fn test_unblock_and_restore() {
+        let temp_dir = tempdir().unwrap();
+        let target = temp_dir.path().join("target");
+        std::fs::create_dir(&target).unwrap();
+        let first = target.join("first.zip");
+        let second = target.join("second.zip");
+        File::create(&first).unwrap();
+        File::create(&second).unwrap();
+        let journal = temp_dir.path().join("journal.jsonl").to_string_lossy().to_string();
+
+        let first_stream = b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/first.zip\r\n";
+        let second_stream = b"[ZoneTransfer]\r\nZoneId=4\r\n";
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&first, ZONE_IDENTIFIER_STREAM, first_stream).unwrap();
+        backend.write_stream(&second, ZONE_IDENTIFIER_STREAM, second_stream).unwrap();
+
+        let mut config = Config::new(false, None, target.to_string_lossy().to_string()).unwrap();
+        config.backend = backend.clone();
+        config.journal_path = Some(journal.clone());
+
+        let stats = process_target(&target.to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 2);
+        assert_eq!(stats.files_journaled, 2);
+        assert!(!backend.has_stream(&first, ZONE_IDENTIFIER_STREAM));
+        assert_eq!(read_journal(&journal).unwrap().len(), 2);
+
+        // Only restore the first file
+        let filter = JournalFilter {
+            path_prefix: Some(first.clone()),
+            ..Default::default()
+        };
+        let stats = restore_journal(&journal, &filter, &mut config).unwrap();
+        assert_eq!(stats.files_processed, 1);
+        assert_eq!(stats.files_restored, 1);
+        assert_eq!(backend.read_stream(&first, ZONE_IDENTIFIER_STREAM).unwrap(), first_stream);
+        assert!(!backend.has_stream(&second, ZONE_IDENTIFIER_STREAM));
+
+        let stats = restore_journal(&journal, &JournalFilter::default(), &mut config).unwrap();
+        assert_eq!(stats.files_restored, 2);
+        assert_eq!(backend.read_stream(&second, ZONE_IDENTIFIER_STREAM).unwrap(), second_stream);
+    }
+
+    #[test]
+    fn test_writer_keeps_one_file_open() {
+        let temp_dir = tempdir().unwrap();
+        let first = temp_dir.path().join("first.jsonl").to_string_lossy().to_string();
+        let second = temp_dir.path().join("second.jsonl").to_string_lossy().to_string();
+        let entry = JournalEntry::new(&temp_dir.path().join("a.zip"), b"[ZoneTransfer]\r\nZoneId=3\r\n");
+
+        let writer = JournalWriter::new();
+        for _ in 0..SYNC_BATCH + 1 {
+            writer.append(&first, &entry).unwrap();
+        }
+        assert_eq!(writer.file.lock().unwrap().as_ref().unwrap().unsynced, 1);
+        assert_eq!(read_journal(&first).unwrap().len(), SYNC_BATCH + 1);
+
+        // Switching journals syncs the previous one first
+        writer.append(&second, &entry).unwrap();
+        writer.sync().unwrap();
+        assert_eq!(writer.file.lock().unwrap().as_ref().unwrap().unsynced, 0);
+        assert_eq!(read_journal(&second).unwrap(), vec![entry]);
+    }
+
+    #[test]
+    fn test_restore_missing_file_fails() {
+        let temp_dir = tempdir().unwrap();
+        let journal = temp_dir.path().join("journal.jsonl").to_string_lossy().to_string();
+        let missing = temp_dir.path().join("deleted.exe");
+        let writer = JournalWriter::new();
+        writer.append(&journal, &JournalEntry::new(&missing, b"[ZoneTransfer]\r\nZoneId=3\r\n")).unwrap();
+        writer.sync().unwrap();
+
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.backend = Arc::new(MemoryStreamBackend::new());
+
+        let stats = restore_journal(&journal, &JournalFilter::default(), &mut config).unwrap();
+        assert_eq!(stats.files_failed, 1);
+        assert_eq!(stats.files_restored, 0);
+    }
+
+    #[test]
+    fn test_restore_uses_earliest_entry() {
+        let temp_dir = tempdir().unwrap();
+        let journal = temp_dir.path().join("journal.jsonl").to_string_lossy().to_string();
+        let file = temp_dir.path().join("setup.zip");
+        File::create(&file).unwrap();
+
+        // Downgraded in one run, then unblocked in a later one
+        let original = b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/setup.zip\r\n";
+        let writer = JournalWriter::new();
+        writer.append(&journal, &JournalEntry::new(&file, original)).unwrap();
+        writer.append(&journal, &JournalEntry::new(&file, b"[ZoneTransfer]\r\nZoneId=1\r\n")).unwrap();
+        writer.sync().unwrap();
+
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        let backend = Arc::new(MemoryStreamBackend::new());
+        config.backend = backend.clone();
+
+        let stats = restore_journal(&journal, &JournalFilter::default(), &mut config).unwrap();
+        assert_eq!(stats.files_processed, 1);
+        assert_eq!(stats.files_restored, 1);
+        assert_eq!(backend.read_stream(&file, ZONE_IDENTIFIER_STREAM).unwrap(), original);
+    }
+}
@@ -3,16 +3,45 @@
 //! This crate provides functionality to unblock files that have been marked as downloaded
 //! from the internet by Windows, by removing their Zone.Identifier alternate data stream.
 
+pub mod backend;
+pub mod cancel;
+pub mod checksum;
 pub mod config;
+pub mod config_file;
 pub mod elevation;
 pub mod error;
+pub mod events;
+pub mod exit_code;
+pub mod filter;
+pub mod journal;
+pub mod logging;
+pub mod observer;
+pub mod origin;
 pub mod path_utils;
+pub mod report;
+pub mod risk;
+pub mod rotation;
+pub mod scan_hook;
+pub mod scanner;
+pub mod targets;
 pub mod ui;
 pub mod unblocker;
+pub mod walker;
+pub mod watch;
+pub mod zone;
 
-pub use config::Config;
+pub use backend::{MemoryStreamBackend, NtfsStreamBackend, StreamBackend};
+#[cfg(unix)]
+pub use backend::SambaXattrBackend;
+pub use cancel::{CancelReason, CancelToken};
+pub use config::{Config, OutputFormat};
 pub use error::{Result, UnblockerError};
-pub use unblocker::{process_target, UnblockStats};
+pub use exit_code::ExitCode;
+pub use journal::{restore_journal, JournalFilter};
+pub use observer::Observer;
+pub use scanner::{scan_target, ScanReport};
+pub use unblocker::{block_target, process_target, FileOutcome, FileRecord, UnblockStats};
+pub use zone::{Zone, ZoneInfo};
 
 /// Application metadata
 pub const APP_NAME: &str = "SaltSpectre's File Unblocker";
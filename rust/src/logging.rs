@@ -0,0 +1,446 @@
+//! Unified logging to the console and a single buffered log file.
+
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use chrono::{Local, Utc};
+use log::{Level, LevelFilter, Metadata, Record};
+use crate::rotation::{rotate, LogRotation};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Target of messages logged through [`crate::ui::log_message`]
+pub const LOG_TARGET: &str = "unblocker";
+
+/// Longest time a buffered line may wait before it is written to the log file
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much is written to the console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Nothing, not even error dialogs (`-q`)
+    Quiet,
+    /// Only error and warning dialogs when there is no log file
+    #[default]
+    Normal,
+    /// Every message (`-v`)
+    Verbose,
+    /// Every message including debug details, also in the log file (`-vv`)
+    Debug,
+}
+
+impl Verbosity {
+    /// Verbosity selected by the number of `-v` flags and `-q`
+    pub fn from_flags(verbose: u8, quiet: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    /// Most detailed level written to the console, if any
+    fn console_level(self) -> Option<Level> {
+        match self {
+            Verbosity::Quiet | Verbosity::Normal => None,
+            Verbosity::Verbose => Some(Level::Info),
+            Verbosity::Debug => Some(Level::Debug),
+        }
+    }
+
+    /// Most detailed level written to the log file
+    fn file_level(self) -> Level {
+        if self == Verbosity::Debug {
+            Level::Debug
+        } else {
+            Level::Info
+        }
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = UnblockerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "quiet" => Ok(Verbosity::Quiet),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            "debug" => Ok(Verbosity::Debug),
+            _ => Err(UnblockerError::Config(format!("Unknown verbosity: {}", s))),
+        }
+    }
+}
+
+/// Layout of log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// `[time] LEVEL: message`, with no prefix for ordinary messages
+    #[default]
+    Text,
+    /// One JSON object per line with `time`, `level`, `target` and `message`
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = UnblockerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" | "jsonl" => Ok(LogFormat::Json),
+            _ => Err(UnblockerError::Config(format!("Unknown log format: {}", s))),
+        }
+    }
+}
+
+/// Time zone of log timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogTime {
+    #[default]
+    Utc,
+    Local,
+}
+
+impl FromStr for LogTime {
+    type Err = UnblockerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utc" => Ok(LogTime::Utc),
+            "local" => Ok(LogTime::Local),
+            _ => Err(UnblockerError::Config(format!("Unknown log time zone: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    last_flush: Instant,
+    /// Size of the file as far as this process knows, including buffered lines
+    size: u64,
+    created: Option<SystemTime>,
+}
+
+impl LogFile {
+    /// Open the log for appending, rotating it first if it is already due
+    fn open(path: &Path, rotation: &LogRotation, incoming: u64) -> std::io::Result<Self> {
+        let existing = fs::metadata(path).ok();
+        if existing.as_ref().is_some_and(|metadata| {
+            rotation.is_due(metadata.len(), incoming, metadata.created().ok())
+        }) {
+            rotate(path, rotation, incoming)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+
+        // Windows hands a file re-created under a just-rotated name the old creation time
+        #[cfg(windows)]
+        if metadata.len() == 0 {
+            use std::os::windows::fs::FileTimesExt;
+            let _ = file.set_times(std::fs::FileTimes::new().set_created(SystemTime::now()));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            last_flush: Instant::now(),
+            size: metadata.len(),
+            created: if metadata.len() == 0 { Some(SystemTime::now()) } else { metadata.created().ok() },
+        })
+    }
+
+    /// Pick up lines written by other processes, or check whether one rotated the file away
+    ///
+    /// Returns false when the log path now names a different, smaller file.
+    fn refresh(&mut self) -> std::io::Result<bool> {
+        let size = self.writer.get_ref().metadata()?.len();
+        if fs::metadata(&self.path).map_or(true, |metadata| metadata.len() < size) {
+            return Ok(false);
+        }
+        self.size = size;
+        Ok(true)
+    }
+}
+
+/// Log sink shared by every clone of a configuration
+///
+/// The log file is opened on first use and kept open for the whole run.
+/// Lines are buffered and written at least once a second, and at once for
+/// warnings and errors.
+#[derive(Debug, Default)]
+pub struct Logger {
+    file: Arc<Mutex<Option<LogFile>>>,
+    /// Set once the background flusher is running
+    flusher: OnceLock<()>,
+}
+
+impl Logger {
+    /// Write a message to the console and log file as the configuration asks
+    pub fn log(&self, level: Level, target: &str, message: &str, config: &Config) -> Result<()> {
+        let to_console = config.verbosity.console_level().is_some_and(|max| level <= max);
+        let log_path = config.log_path.as_deref().filter(|_| level <= config.verbosity.file_level());
+        // An observer replaces the console, and also the dialogs that show warnings and errors
+        let observer = config.observer.as_ref()
+            .filter(|_| to_console || (level <= Level::Warn && config.verbosity != Verbosity::Quiet));
+        if !to_console && log_path.is_none() && observer.is_none() {
+            return Ok(());
+        }
+
+        if let Some(observer) = observer {
+            observer.message(level, message);
+        }
+
+        let line = format_line(level, target, message, config);
+
+        if to_console && config.observer.is_none() {
+            if level <= Level::Warn {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        if let Some(log_path) = log_path {
+            self.write_file(Path::new(log_path), &line, level <= Level::Warn, config)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, line: &str, flush_now: bool, config: &Config) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let line = format!("{}\n", line);
+        let incoming = line.len() as u64;
+        let rotation = &config.log_rotation;
+
+        // A different path means the configuration changed, and a full or old file is rotated
+        let reopen = file.as_ref().is_some_and(|open| {
+            open.path != path || (rotation.is_enabled() && rotation.is_due(open.size, incoming, open.created))
+        });
+        if reopen {
+            if let Some(mut old) = file.take() {
+                old.writer.flush()?;
+            }
+        }
+        let open = match file.as_mut() {
+            Some(open) => open,
+            None => {
+                self.flusher.get_or_init(|| self.start_flusher());
+                let opened = LogFile::open(path, rotation, incoming).or_else(|e| {
+                    // Rotation trouble must not stop the run; keep appending to the current file
+                    let warning = format_line(Level::Warn, LOG_TARGET, &format!("Log rotation failed: {}", e), config);
+                    let mut open = LogFile::open(path, &LogRotation::default(), incoming)?;
+                    open.writer.write_all(format!("{}\n", warning).as_bytes())?;
+                    open.size += warning.len() as u64 + 1;
+                    Ok::<_, std::io::Error>(open)
+                })?;
+                file.insert(opened)
+            }
+        };
+
+        open.writer.write_all(line.as_bytes())?;
+        open.size += incoming;
+        if flush_now || open.last_flush.elapsed() >= FLUSH_INTERVAL {
+            open.writer.flush()?;
+            open.last_flush = Instant::now();
+            // Lines written after another process rotated would end up in the rotated file
+            if !open.refresh()? {
+                *file = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the log file once a second, so lines do not wait for the next message
+    ///
+    /// The thread ends once the logger is dropped.
+    fn start_flusher(&self) {
+        let file = Arc::downgrade(&self.file);
+        thread::spawn(move || loop {
+            thread::sleep(FLUSH_INTERVAL);
+            let Some(file) = file.upgrade() else {
+                break;
+            };
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(open) = file.as_mut() {
+                let _ = open.writer.flush();
+                open.last_flush = Instant::now();
+            }
+        });
+    }
+
+    /// Write every buffered line to the log file
+    pub fn flush(&self) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(open) = file.as_mut() {
+            open.writer.flush()?;
+            open.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// Format one log line in the configured layout and time zone
+fn format_line(level: Level, target: &str, message: &str, config: &Config) -> String {
+    match config.log_format {
+        LogFormat::Text => {
+            let time = match config.log_time {
+                LogTime::Utc => Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+                LogTime::Local => Local::now().format("%Y-%m-%d %H:%M:%S %:z").to_string(),
+            };
+            let prefix = match level {
+                Level::Error => "ERROR: ",
+                Level::Warn => "WARNING: ",
+                Level::Info => "",
+                Level::Debug | Level::Trace => "DEBUG: ",
+            };
+            format!("[{}] {}{}", time, prefix, message)
+        }
+        LogFormat::Json => {
+            let time = match config.log_time {
+                LogTime::Utc => Utc::now().to_rfc3339(),
+                LogTime::Local => Local::now().to_rfc3339(),
+            };
+            serde_json::json!({
+                "time": time,
+                "level": level.as_str().to_ascii_lowercase(),
+                "target": target,
+                "message": message,
+            }).to_string()
+        }
+    }
+}
+
+/// Configuration whose logger receives `log` macro records
+static INSTALLED: OnceLock<Config> = OnceLock::new();
+
+/// Forwards `log` macro records, e.g. `log::warn!`, to the installed configuration
+struct Bridge;
+
+static BRIDGE: Bridge = Bridge;
+
+impl log::Log for Bridge {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies only get through with warnings and errors
+        metadata.target().starts_with(LOG_TARGET) || metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if let (true, Some(config)) = (self.enabled(record.metadata()), INSTALLED.get()) {
+            let message = record.args().to_string();
+            let _ = config.logger.log(record.level(), record.target(), &message, config);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(config) = INSTALLED.get() {
+            let _ = config.logger.flush();
+        }
+    }
+}
+
+/// Route `log` macro records to the console and log file of `config`
+///
+/// Only the first call in a process takes effect.
+pub fn install(config: &Config) {
+    if INSTALLED.set(config.clone()).is_ok() && log::set_logger(&BRIDGE).is_ok() {
+        log::set_max_level(if config.verbosity == Verbosity::Debug { LevelFilter::Debug } else { LevelFilter::Info });
+    }
+}
+
+/// The configuration passed to [`install`], once logging is set up
+pub fn installed() -> Option<&'static Config> {
+    INSTALLED.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_levels_and_formats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_path = temp_dir.path().join("run.log");
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.log_path = Some(log_path.to_string_lossy().to_string());
+        config.verbosity = Verbosity::Quiet;
+
+        config.logger.log(Level::Info, LOG_TARGET, "kept", &config).unwrap();
+        config.logger.log(Level::Debug, LOG_TARGET, "dropped", &config).unwrap();
+        config.logger.log(Level::Warn, "unblocker::path_utils", "careful", &config).unwrap();
+        config.log_format = LogFormat::Json;
+        config.log_time = LogTime::Local;
+        config.logger.log(Level::Error, LOG_TARGET, "broken", &config).unwrap();
+        config.logger.flush().unwrap();
+
+        let contents = fs::read_to_string(&log_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", contents);
+        assert!(lines[0].ends_with(" UTC] kept"), "{}", lines[0]);
+        assert!(lines[1].ends_with("] WARNING: careful"), "{}", lines[1]);
+        let json: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(json["level"], "error");
+        assert_eq!(json["message"], "broken");
+        assert!(json["time"].is_string());
+
+        assert_eq!(Verbosity::from_flags(2, false), Verbosity::Debug);
+        assert_eq!(Verbosity::from_flags(1, true), Verbosity::Quiet);
+        assert_eq!("LOCAL".parse::<LogTime>().unwrap(), LogTime::Local);
+    }
+
+    #[test]
+    fn test_idle_lines_are_flushed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_path = temp_dir.path().join("watch.log");
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.log_path = Some(log_path.to_string_lossy().to_string());
+
+        config.logger.log(Level::Info, LOG_TARGET, "waiting for changes", &config).unwrap();
+        std::thread::sleep(FLUSH_INTERVAL + Duration::from_millis(500));
+        assert!(fs::read_to_string(&log_path).unwrap().contains("waiting for changes"));
+    }
+
+    #[test]
+    fn test_rotation_shared_by_two_runs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_path = temp_dir.path().join("run.log");
+        // Two configurations with their own loggers stand in for the parent and the elevated child
+        let runs: Vec<Config> = (0..2).map(|_| {
+            let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+            config.log_path = Some(log_path.to_string_lossy().to_string());
+            config.verbosity = Verbosity::Quiet;
+            config.log_rotation = LogRotation { max_size: Some(512), keep: 100, ..Default::default() };
+            config
+        }).collect();
+
+        std::thread::scope(|scope| {
+            for (run, config) in runs.iter().enumerate() {
+                scope.spawn(move || {
+                    for line in 0..100 {
+                        config.logger.log(Level::Warn, LOG_TARGET, &format!("run {} line {}", run, line), config).unwrap();
+                    }
+                    config.logger.flush().unwrap();
+                });
+            }
+        });
+
+        // Every line survives, whichever file it ended up in
+        let mut lines = 0;
+        for entry in fs::read_dir(temp_dir.path()).unwrap() {
+            lines += fs::read_to_string(entry.unwrap().path()).unwrap().lines().count();
+        }
+        assert_eq!(lines, 200);
+        assert!(log_path.with_extension("log.1").exists());
+        assert!(!log_path.with_extension("log.lock").exists());
+    }
+}
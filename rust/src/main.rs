@@ -5,15 +5,32 @@
 #[cfg(not(windows))]
 compile_error!("This application is designed for Windows only. Use cross-compilation targets like x86_64-pc-windows-msvc or aarch64-pc-windows-msvc");
 
+use chrono::{DateTime, Utc};
 use clap::{Arg, Command};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
 use unblocker::{
-    config::Config,
-    elevation::{is_elevated, relaunch_as_admin},
+    cancel::{CancelReason, CancelToken},
+    checksum::HashManifest,
+    config::{Config, OutputFormat},
+    config_file::{portable_config_path, ConfigFile, PORTABLE_CONFIG_NAME},
+    elevation::{is_elevated, relaunch_as_admin_with},
     error::{Result, UnblockerError},
+    exit_code::ExitCode,
+    journal::{restore_journal, JournalFilter},
+    logging::{self, LogFormat, LogTime, Verbosity},
+    rotation,
+    events::{emit, Event},
+    report::RunReport,
+    scanner::{scan_target, ScanReport},
+    targets::{dedupe_roots, expand_response_files, process_roots, read_path_list, write_path_list, TargetStats},
     ui::{log_message, show_error},
-    unblocker::process_target,
+    unblocker::{block_target, process_target, UnblockStats},
+    watch::{watch_targets, DEFAULT_DEBOUNCE},
+    zone::{Zone, ZoneInfo},
     APP_NAME, APP_VERSION, APP_DESCRIPTION,
 };
 
@@ -21,15 +38,18 @@ use unblocker::{
 use windows::Win32::System::Console::{AttachConsole, AllocConsole, ATTACH_PARENT_PROCESS};
 
 /// Attach to parent console or allocate new one if needed
+///
+/// Only the first call has an effect.
 #[cfg(windows)]
 fn ensure_console() {
-    unsafe {
+    static CONSOLE: std::sync::Once = std::sync::Once::new();
+    CONSOLE.call_once(|| unsafe {
         // Try to attach to parent process console (if launched from cmd/powershell)
         if AttachConsole(ATTACH_PARENT_PROCESS).is_err() {
             // If no parent console, try to allocate a new one
             let _ = AllocConsole();
         }
-    }
+    });
 }
 
 #[cfg(not(windows))]
@@ -38,51 +58,107 @@ fn ensure_console() {
 }
 
 fn main() {
-    // Check if --verbose flag is present before parsing full arguments
-    let needs_console = std::env::args().any(|arg| arg == "--verbose" || arg == "-v");
+    // Response files are expanded first so their options count as well
+    let args = expand_response_files(std::env::args());
+    
+    // Check if -v, --scan, --block or --format is present before parsing full arguments, so that
+    // their parse errors are shown; `run` checks again once the config file is merged
+    let needs_console = args.as_ref().map_or(true, |args| {
+        args.iter().any(|arg| {
+            arg == "--verbose"
+                || arg == "--scan"
+                || arg.starts_with("--block")
+                || arg.starts_with("--format")
+                || (arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v'))
+        })
+    });
 
     if needs_console {
         ensure_console();
     }
 
-    env_logger::init();
-    
-    if let Err(e) = run() {
-        let error_msg = e.user_message();
-        eprintln!("Error: {}", error_msg);
-        
-        // Try to create a minimal config for error display
-        let config = Config::new(true, None, ".".to_string()).unwrap_or_else(|_| {
-            // Fallback config that should always work
-            Config {
-                verbose: true,
-                log_path: None,
+    match args.and_then(run) {
+        Ok(code) => {
+            log::logger().flush();
+            process::exit(code)
+        }
+        Err(e) => {
+            let error_msg = e.user_message();
+            
+            // Errors after the configuration was loaded follow its console and log file settings
+            let config = logging::installed().cloned().unwrap_or_else(|| Config {
+                verbosity: Verbosity::Verbose,
                 target_path: ".".to_string(),
-                requires_elevation: false,
+                ..Config::default()
+            });
+            
+            if config.verbosity == Verbosity::Normal {
+                eprintln!("Error: {}", error_msg);
             }
-        });
-        
-        show_error(&error_msg, &config);
-        process::exit(1);
+            show_error(&error_msg, &config);
+            let _ = config.logger.flush();
+            process::exit(ExitCode::from_error(&e).code());
+        }
     }
 }
 
-fn run() -> Result<()> {
+/// Run the command line and return the process exit code, see [`ExitCode`]
+fn run(args: Vec<String>) -> Result<i32> {
     let matches = Command::new(APP_NAME)
         .version(APP_VERSION)
         .author("SaltSpectre")
         .about(APP_DESCRIPTION)
         .arg(
             Arg::new("path")
-                .help("File or directory path to unblock")
-                .required(true)
+                .help("Files or directories to unblock; @FILE reads further arguments from FILE, one per line")
+                .num_args(1..)
+                .required_unless_present("files-from")
                 .index(1),
         )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help(format!("TOML config file with defaults and profiles (default: {} next to the executable, if present)", PORTABLE_CONFIG_NAME))
+                .value_name("FILE")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("Apply the named [profiles.<NAME>] section of the config file")
+                .value_name("NAME")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("files-from")
+                .long("files-from")
+                .help("Read more target paths from FILE, one per line or NUL-separated; - reads standard input")
+                .value_name("FILE")
+                .num_args(1),
+        )
         .arg(
             Arg::new("verbose")
+                .short('v')
                 .long("verbose")
-                .help("Enable verbose output")
-                .action(clap::ArgAction::SetTrue),
+                .help("Show every log message on the console; repeat (-vv) to add debug details")
+                .action(clap::ArgAction::Count),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Show nothing on the console and no dialogs; the log file and exit code still report the result")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            Arg::new("verbosity")
+                .long("verbosity")
+                .help("Console output: quiet, normal, verbose or debug; overrides the configuration file like -q, -v and -vv")
+                .value_name("LEVEL")
+                .num_args(1)
+                .conflicts_with_all(["verbose", "quiet", "format"])
+                .value_parser(|s: &str| s.parse::<Verbosity>().map_err(|e| e.user_message())),
         )
         .arg(
             Arg::new("log")
@@ -91,27 +167,579 @@ fn run() -> Result<()> {
                 .value_name("FILE")
                 .num_args(1),
         )
-        .get_matches();
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .help("Layout of log lines: text or json (one JSON object per line)")
+                .value_name("FORMAT")
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<LogFormat>().map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("log-time")
+                .long("log-time")
+                .help("Time zone of log timestamps: utc or local")
+                .value_name("ZONE")
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<LogTime>().map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("log-max-size")
+                .long("log-max-size")
+                .help("Rotate the log file before it grows beyond this size, e.g. 10MB or 512KB")
+                .value_name("SIZE")
+                .num_args(1)
+                .value_parser(|s: &str| rotation::parse_size(s).map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("log-max-age")
+                .long("log-max-age")
+                .help("Rotate the log file once it is this old, e.g. 7d, 12h or 30m")
+                .value_name("AGE")
+                .num_args(1)
+                .value_parser(|s: &str| rotation::parse_duration(s).map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("log-keep")
+                .long("log-keep")
+                .help("Number of rotated log files to keep (default: 5)")
+                .value_name("N")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("log-compress")
+                .long("log-compress")
+                .help("Compress rotated log files with gzip")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-log-compress")
+                .long("no-log-compress")
+                .help("Turn off --log-compress, e.g. when the configuration file turns it on")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("log-compress"),
+        )
+        .arg(
+            Arg::new("time-limit")
+                .long("time-limit")
+                .help("Stop after the current file once the run has taken this long, e.g. 90m or 2h, and report the files processed so far")
+                .value_name("DURATION")
+                .num_args(1)
+                .value_parser(|s: &str| rotation::parse_duration(s).map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format for results: text, json (a versioned document with every file's outcome) or ndjson (one event per line as the run progresses)")
+                .value_name("FORMAT")
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<OutputFormat>().map_err(|e| e.user_message()))
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            Arg::new("scan")
+                .long("scan")
+                .help("Report blocked files without unblocking them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("zones")
+                .long("zones")
+                .help("Only unblock files from these zones, comma separated (e.g. internet or 3); other zones stay blocked")
+                .value_name("ZONES")
+                .num_args(1)
+                .value_delimiter(',')
+                .value_parser(|s: &str| s.parse::<Zone>().map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("downgrade-to")
+                .long("downgrade-to")
+                .help("Rewrite Zone.Identifier to a lower zone (e.g. intranet) instead of removing it, keeping HostUrl and ReferrerUrl")
+                .value_name("ZONE")
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<Zone>().map_err(|e| e.user_message())),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Apply every check and report what would happen without changing any file")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("restore"),
+        )
+        .arg(
+            Arg::new("no-dry-run")
+                .long("no-dry-run")
+                .help("Turn off --dry-run, e.g. when the configuration file turns it on")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("dry-run"),
+        )
+        .arg(
+            Arg::new("allow-risky")
+                .long("allow-risky")
+                .help("Also unblock high-risk file types such as executables, scripts, shortcuts and macro-enabled Office files")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-allow-risky")
+                .long("no-allow-risky")
+                .help("Turn off --allow-risky, e.g. when the configuration file turns it on")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("allow-risky"),
+        )
+        .arg(
+            Arg::new("risky-extensions")
+                .long("risky-extensions")
+                .help("Comma-separated extensions treated as high-risk, replacing the built-in list")
+                .value_name("LIST")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Only process files matching this glob, relative to the target (repeatable, case-insensitive)")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Skip files and directories matching this glob, relative to the target (repeatable, case-insensitive)")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("trust-origin")
+                .long("trust-origin")
+                .help("Only unblock files whose HostUrl or ReferrerUrl is on this domain (and its subdomains) or starts with this URL (repeatable)")
+                .value_name("PATTERN")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("deny-origin")
+                .long("deny-origin")
+                .help("Keep files blocked when their HostUrl or ReferrerUrl matches this domain or URL pattern (repeatable)")
+                .value_name("PATTERN")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("hash-manifest")
+                .long("hash-manifest")
+                .help("Only unblock files whose SHA-256 is listed in this manifest (sha256sum format)")
+                .value_name("FILE")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("verify-checksums")
+                .long("verify-checksums")
+                .help("Only unblock files matching a SHA256SUMS or <file>.sha256 checksum file next to them")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-verify-checksums")
+                .long("no-verify-checksums")
+                .help("Turn off --verify-checksums, e.g. when the configuration file turns it on")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("verify-checksums"),
+        )
+        .arg(
+            Arg::new("scan-command")
+                .long("scan-command")
+                .help("Run this command on each file first and unblock only if it exits 0; {file} is replaced by the path, which is appended otherwise")
+                .value_name("COMMAND")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("scan-timeout")
+                .long("scan-timeout")
+                .help("Seconds the scan command may take per file before the file is kept blocked (default: 60)")
+                .value_name("SECONDS")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("scan-concurrency")
+                .long("scan-concurrency")
+                .help("Maximum number of scan commands running at once (default: 1)")
+                .value_name("N")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Keep running and unblock files as they are created or finish writing in the target directories")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["scan", "restore", "block"]),
+        )
+        .arg(
+            Arg::new("debounce")
+                .long("debounce")
+                .help("Milliseconds a file must stay unchanged before watch mode processes it (default: 2000)")
+                .value_name("MS")
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64))
+                .requires("watch"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .help("Number of worker threads for directory traversal (0 = one per CPU core)")
+                .value_name("N")
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("journal")
+                .long("journal")
+                .help("Record every Zone.Identifier stream in an undo journal before changing it")
+                .value_name("FILE")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Put back the streams recorded in an undo journal for files under the given path")
+                .value_name("JOURNAL")
+                .num_args(1)
+                .conflicts_with_all(["scan", "zones", "downgrade-to", "journal"]),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Only restore journal entries recorded at or after this RFC 3339 time")
+                .value_name("TIME")
+                .num_args(1)
+                .value_parser(parse_time)
+                .requires("restore"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Only restore journal entries recorded at or before this RFC 3339 time")
+                .value_name("TIME")
+                .num_args(1)
+                .value_parser(parse_time)
+                .requires("restore"),
+        )
+        .arg(
+            Arg::new("block")
+                .long("block")
+                .help("Mark files as downloaded from the given zone (e.g. 3 or internet) instead of unblocking them")
+                .value_name("ZONE")
+                .num_args(1)
+                .value_parser(|s: &str| s.parse::<Zone>().map_err(|e| e.user_message()))
+                .conflicts_with_all(["scan", "zones", "downgrade-to", "restore"]),
+        )
+        .arg(
+            Arg::new("host-url")
+                .long("host-url")
+                .help("HostUrl to record when blocking files")
+                .value_name("URL")
+                .num_args(1)
+                .requires("block"),
+        )
+        .arg(
+            Arg::new("referrer-url")
+                .long("referrer-url")
+                .help("ReferrerUrl to record when blocking files")
+                .value_name("URL")
+                .num_args(1)
+                .requires("block"),
+        )
+        .get_matches_from(&args);
 
-    let target_path = matches.get_one::<String>("path")
-        .ok_or_else(|| UnblockerError::Config("Path argument is required".to_string()))?
+    let mut roots: Vec<String> = matches.get_many::<String>("path")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
+    let listed = match matches.get_one::<String>("files-from") {
+        Some(source) => read_path_list(source)?,
+        None => Vec::new(),
+    };
+    roots.extend(listed.iter().cloned());
+    let roots = dedupe_roots(roots);
+    
+    // A missing root is reported as failed with the others; the run only stops when none exists
+    let first_root = roots.iter()
+        .find(|root| Path::new(root).exists())
+        .or(roots.first())
+        .ok_or_else(|| UnblockerError::Config("No target paths given".to_string()))?
         .clone();
     
-    let mut config = Config::new(
-        matches.get_flag("verbose"),
-        matches.get_one::<String>("log").cloned(),
-        target_path,
-    )?;
+    let mut config = Config::new(false, None, first_root)?;
+    
+    // Config file defaults and profile first, so that command line flags override them
+    let config_path = matches.get_one::<String>("config").map(PathBuf::from).or_else(portable_config_path);
+    let profile = matches.get_one::<String>("profile").map(String::as_str);
+    match &config_path {
+        Some(path) => ConfigFile::load(path)?.apply(profile, &mut config)?,
+        None if profile.is_some() => {
+            return Err(UnblockerError::Config(format!(
+                "--profile requires a config file; pass --config or place {} next to the executable",
+                PORTABLE_CONFIG_NAME
+            )));
+        }
+        None => {}
+    }
+    
+    let (verbose, quiet) = (matches.get_count("verbose"), matches.get_flag("quiet"));
+    if verbose > 0 || quiet {
+        config.verbosity = Verbosity::from_flags(verbose, quiet);
+    }
+    if let Some(verbosity) = matches.get_one::<Verbosity>("verbosity") {
+        config.verbosity = *verbosity;
+    }
+    if let Some(log) = matches.get_one::<String>("log") {
+        config.log_path = Some(log.clone());
+    }
+    if let Some(format) = matches.get_one::<LogFormat>("log-format") {
+        config.log_format = *format;
+    }
+    if let Some(time) = matches.get_one::<LogTime>("log-time") {
+        config.log_time = *time;
+    }
+    if let Some(size) = matches.get_one::<u64>("log-max-size") {
+        config.log_rotation.max_size = Some(*size);
+    }
+    if let Some(age) = matches.get_one::<Duration>("log-max-age") {
+        config.log_rotation.max_age = Some(*age);
+    }
+    if let Some(keep) = matches.get_one::<usize>("log-keep") {
+        config.log_rotation.keep = *keep;
+    }
+    if let Some(on) = switch(&matches, "log-compress") {
+        config.log_rotation.compress = on;
+    }
+    if let Some(zones) = matches.get_many::<Zone>("zones") {
+        config.unblock_zones = Some(zones.copied().collect());
+    }
+    if let Some(zone) = matches.get_one::<Zone>("downgrade-to") {
+        config.downgrade_to = Some(*zone);
+    }
+    if let Some(journal) = matches.get_one::<String>("journal") {
+        config.journal_path = Some(journal.clone());
+    }
+    if let Some(on) = switch(&matches, "dry-run") {
+        config.dry_run = on;
+    }
+    if let Some(on) = switch(&matches, "allow-risky") {
+        config.allow_risky = on;
+    }
+    if let Some(extensions) = matches.get_many::<String>("risky-extensions") {
+        config.risky_extensions = extensions.map(|ext| ext.trim().to_string()).collect();
+    }
+    if let Some(globs) = matches.get_many::<String>("include") {
+        config.include = globs.cloned().collect();
+    }
+    if let Some(globs) = matches.get_many::<String>("exclude") {
+        config.exclude = globs.cloned().collect();
+    }
+    if let Some(origins) = matches.get_many::<String>("trust-origin") {
+        config.trusted_origins = origins.cloned().collect();
+    }
+    if let Some(origins) = matches.get_many::<String>("deny-origin") {
+        config.denied_origins = origins.cloned().collect();
+    }
+    if let Some(manifest) = matches.get_one::<String>("hash-manifest") {
+        config.hash_manifest = Some(Arc::new(HashManifest::load(Path::new(manifest))?));
+    }
+    if let Some(on) = switch(&matches, "verify-checksums") {
+        config.verify_checksums = on;
+    }
+    if let Some(command) = matches.get_one::<String>("scan-command") {
+        config.scan_command = Some(command.clone());
+    }
+    if let Some(seconds) = matches.get_one::<u64>("scan-timeout") {
+        config.scan_timeout = Duration::from_secs(*seconds);
+    }
+    if let Some(concurrency) = matches.get_one::<usize>("scan-concurrency") {
+        config.scan_concurrency = *concurrency;
+    }
+    if let Some(threads) = matches.get_one::<usize>("threads") {
+        config.workers = *threads;
+    }
+    if let Some(limit) = matches.get_one::<Duration>("time-limit") {
+        config.cancel.set_time_limit(*limit);
+    }
+    if let Some(format) = matches.get_one::<OutputFormat>("format") {
+        config.output_format = *format;
+    }
+    
+    // Console logging and machine-readable output exclude each other, so the one given on the command line wins
+    if config.verbosity >= Verbosity::Verbose && config.output_format != OutputFormat::Text {
+        let cli_verbosity = verbose > 0 || quiet || matches.contains_id("verbosity");
+        if matches.contains_id("format") && !cli_verbosity {
+            config.verbosity = Verbosity::Normal;
+        } else if cli_verbosity && !matches.contains_id("format") {
+            config.output_format = OutputFormat::Text;
+        }
+    }
+    if config.verbosity >= Verbosity::Verbose || config.output_format != OutputFormat::Text {
+        ensure_console();
+    }
+    config.collect_records = config.output_format == OutputFormat::Json;
+    config.validate()?;
+    logging::install(&config);
+    handle_ctrl_c(&config.cancel);
 
-    let stats = process_target(&config.target_path.clone(), &mut config)?;
+    if matches.get_flag("watch") {
+        let debounce = matches.get_one::<u64>("debounce")
+            .map(|ms| Duration::from_millis(*ms))
+            .unwrap_or(DEFAULT_DEBOUNCE);
+        let result = TargetStats {
+            roots: Vec::new(),
+            total: watch_targets(&roots, &mut config, debounce)?,
+        };
+        
+        if config.output_format == OutputFormat::Json {
+            println!("{}", RunReport::new("watch", &result, &config).to_json()?);
+        }
+        emit(&Event::Summary { stats: &result.total }, &config)?;
+        report_stopped(&result.total, &config);
+        return Ok(ExitCode::from_stats(&result.total).code());
+    }
+
+    if matches.get_flag("scan") {
+        let result = process_roots(&roots, &mut config, |root, config| {
+            let report = scan_target(root, config)?;
+            if config.output_format == OutputFormat::Text {
+                for entry in &report.entries {
+                    println!("{}", entry.describe());
+                }
+            }
+            Ok(report.stats)
+        })?;
+        
+        if config.output_format == OutputFormat::Json {
+            println!("{}", RunReport::new("scan", &result, &config).to_json()?);
+        }
+        emit(&Event::Summary { stats: &result.total }, &config)?;
+        report_stopped(&result.total, &config);
+        let report = ScanReport { stats: result.total, entries: Vec::new() };
+        if config.output_format == OutputFormat::Text {
+            println!("{}", report.summary());
+        }
+        log_message(&format!("Scan completed. {}", report.summary()), &config)?;
+        return Ok(ExitCode::from_stats(&report.stats).code());
+    }
+
+    let mode = if matches.contains_id("restore") {
+        "restore"
+    } else if matches.contains_id("block") {
+        "block"
+    } else {
+        "unblock"
+    };
+    let result = if let Some(journal) = matches.get_one::<String>("restore") {
+        let since = matches.get_one::<DateTime<Utc>>("since").copied();
+        let until = matches.get_one::<DateTime<Utc>>("until").copied();
+        process_roots(&roots, &mut config, |root, config| {
+            let filter = JournalFilter {
+                path_prefix: Some(root.into()),
+                since,
+                until,
+            };
+            restore_journal(journal, &filter, config)
+        })?
+    } else if let Some(zone) = matches.get_one::<Zone>("block") {
+        let mut zone_info = ZoneInfo::new(*zone);
+        zone_info.host_url = matches.get_one::<String>("host-url").cloned();
+        zone_info.referrer_url = matches.get_one::<String>("referrer-url").cloned();
+        process_roots(&roots, &mut config, |root, config| block_target(root, &zone_info, config))?
+    } else {
+        process_roots(&roots, &mut config, process_target)?
+    };
     
-    log_message(&format!("Operation completed. {}", stats.summary()), &config)?;
+    log_message(&format!("Operation completed. {}", result.total.summary()), &config)?;
+    if config.output_format == OutputFormat::Json {
+        println!("{}", RunReport::new(mode, &result, &config).to_json()?);
+    }
+    emit(&Event::Summary { stats: &result.total }, &config)?;
+    report_stopped(&result.total, &config);
 
-    if config.requires_elevation && !is_elevated()? {
+    if config.requires_elevation && result.total.cancelled.is_none() && !is_elevated()? {
         log_message("Some files could not be unblocked due to permission issues. Retrying with admin privileges...", &config)?;
-        relaunch_as_admin()?;
+        // A list read from standard input is handed over in a file, as it may not fit on a command line
+        let list_file = match matches.get_one::<String>("files-from").map(String::as_str) {
+            Some("-") => Some(write_path_list(&listed, &std::env::temp_dir())?),
+            _ => None,
+        };
+        // The elevated instance retries every file, so its exit code describes the run
+        let code = relaunch_as_admin_with(&relaunch_arguments(&args, list_file.as_deref(), &config.cancel));
+        if let Some(list_file) = list_file {
+            let _ = std::fs::remove_file(list_file);
+        }
+        return code;
+    }
+    
+    Ok(ExitCode::from_stats(&result.total).code())
+}
+
+/// Stop after the current file on the first Ctrl-C, and at once on the second
+fn handle_ctrl_c(cancel: &CancelToken) {
+    let cancel = cancel.clone();
+    let installed = ctrlc::set_handler(move || {
+        if cancel.reason() == Some(CancelReason::Requested) {
+            log::logger().flush();
+            process::exit(ExitCode::Cancelled.code());
+        }
+        cancel.cancel();
+        log::warn!("Stopping after the current file; press Ctrl-C again to quit immediately");
+    });
+
+    if let Err(e) = installed {
+        log::debug!("Ctrl-C handler not installed: {}", e);
+    }
+}
+
+/// Tell the user that the totals only cover the files processed before the run was stopped
+fn report_stopped(stats: &UnblockStats, config: &Config) {
+    if stats.cancelled.is_some() && config.verbosity != Verbosity::Quiet && config.output_format == OutputFormat::Text {
+        eprintln!("{}", stats.summary());
+    }
+}
+
+/// Arguments for the elevated instance
+///
+/// A path list read from standard input cannot be read again, so the elevated
+/// instance reads `list_file` instead. It only gets the time that is left of
+/// the time limit.
+fn relaunch_arguments(args: &[String], list_file: Option<&Path>, cancel: &CancelToken) -> Vec<String> {
+    let mut relaunch: Vec<String> = args.iter().skip(1).cloned().collect();
+    
+    if let Some(list_file) = list_file {
+        if let Some(index) = relaunch.iter().position(|arg| arg == "--files-from") {
+            relaunch.drain(index..=index + 1);
+        }
+        relaunch.retain(|arg| arg != "--files-from=-");
+        relaunch.insert(0, format!("--files-from={}", list_file.display()));
     }
     
-    Ok(())
+    if let Some(remaining) = cancel.remaining() {
+        if let Some(index) = relaunch.iter().position(|arg| arg == "--time-limit") {
+            relaunch.drain(index..=index + 1);
+        }
+        relaunch.retain(|arg| !arg.starts_with("--time-limit="));
+        relaunch.insert(0, format!("--time-limit={}s", remaining.as_secs().max(1)));
+    }
+    
+    relaunch
 }
 
+/// `Some(true)` for `--<name>`, `Some(false)` for `--no-<name>` and `None` when neither is given
+fn switch(matches: &clap::ArgMatches, name: &str) -> Option<bool> {
+    if matches.get_flag(name) {
+        Some(true)
+    } else if matches.get_flag(&format!("no-{}", name)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Parse an RFC 3339 timestamp from the command line
+fn parse_time(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid time '{}': {}", value, e))
+}
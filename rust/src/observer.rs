@@ -0,0 +1,165 @@
+//! Progress callbacks for programs that embed the library.
+
+use crate::config::Config;
+use crate::filter::PathFilter;
+use crate::unblocker::{FileFailure, FileRecord, UnblockStats};
+use crate::walker::count_files;
+use log::Level;
+use std::fmt;
+use std::path::Path;
+
+/// Receives the progress of a run instead of the console
+///
+/// Set `Config::observer` to receive these callbacks. They are made on the
+/// thread that called the library, one at a time and in the order results
+/// arrive, even when files are processed by several worker threads. While an
+/// observer is set, messages are handed to [`Observer::message`] and nothing is
+/// printed to the console or shown in a dialog; the log file is still written.
+///
+/// A restore reports the entries it replays as the files of its target. Watch
+/// mode reports `started` for each watched folder when watching begins, without
+/// a total, and `finished` when it stops.
+///
+/// Every method has an empty default, so an implementation only needs the
+/// callbacks it uses.
+pub trait Observer: fmt::Debug + Send + Sync {
+    /// Whether `started` should receive the number of files up front
+    ///
+    /// Counting takes an extra pass over the directory tree before processing starts.
+    fn wants_totals(&self) -> bool {
+        false
+    }
+
+    /// Processing of a file or directory target began
+    ///
+    /// `total` is the number of files that will be reported through `file_result`,
+    /// when `wants_totals` returned true.
+    fn started(&self, _target: &Path, _total: Option<u64>) {}
+
+    /// A file was processed, skipped or failed
+    fn file_result(&self, _record: &FileRecord) {}
+
+    /// A directory could not be enumerated, so the files below it are not processed
+    fn directory_error(&self, _path: &Path, _error: &FileFailure) {}
+
+    /// Processing of a target finished with these statistics
+    fn finished(&self, _target: &Path, _stats: &UnblockStats) {}
+
+    /// A message that would otherwise be written to the console or shown in a dialog
+    fn message(&self, _level: Level, _message: &str) {}
+}
+
+/// Tell the observer, if any, that a target is about to be processed
+pub(crate) fn notify_started(target: &Path, filter: Option<&PathFilter>, config: &Config) {
+    if let Some(observer) = &config.observer {
+        let total = observer.wants_totals().then(|| match filter {
+            Some(filter) => count_files(target, filter),
+            None => 1,
+        });
+        observer.started(target, total);
+    }
+}
+
+/// Tell the observer, if any, that a target is done
+pub(crate) fn notify_finished(target: &Path, stats: &UnblockStats, config: &Config) {
+    if let Some(observer) = &config.observer {
+        observer.finished(target, stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::logging::Verbosity;
+    use crate::journal::{restore_journal, JournalFilter};
+    use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+    use crate::unblocker::{process_target, FileOutcome};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Observer for Recorder {
+        fn wants_totals(&self) -> bool {
+            true
+        }
+
+        fn started(&self, _target: &Path, total: Option<u64>) {
+            self.calls.lock().unwrap().push(format!("started {:?}", total));
+        }
+
+        fn file_result(&self, record: &FileRecord) {
+            let name = record.path.file_name().unwrap().to_string_lossy().to_string();
+            self.calls.lock().unwrap().push(format!("{} {:?}", name, record.outcome.as_ref().ok()));
+        }
+
+        fn finished(&self, _target: &Path, stats: &UnblockStats) {
+            self.calls.lock().unwrap().push(format!("finished {}", stats.files_processed));
+        }
+
+        fn message(&self, level: Level, message: &str) {
+            if message.starts_with("Unblocked") {
+                self.calls.lock().unwrap().push(format!("{} message", level));
+            }
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_whole_run() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let blocked = temp_dir.path().join("a.zip");
+        fs::write(&blocked, b"zip").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), b"text").unwrap();
+
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&blocked, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        let recorder = Arc::new(Recorder::default());
+
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.backend = backend;
+        config.verbosity = Verbosity::Verbose;
+        config.include = vec!["*.zip".to_string()];
+        config.observer = Some(recorder.clone());
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+
+        assert_eq!(stats.files_unblocked, 1);
+        let calls = recorder.calls.lock().unwrap();
+        assert_eq!(calls.first().map(String::as_str), Some("started Some(2)"));
+        assert_eq!(calls.last().map(String::as_str), Some("finished 2"));
+        assert!(calls.contains(&"INFO message".to_string()), "{:?}", calls);
+        assert!(calls.contains(&format!("a.zip {:?}", Some(FileOutcome::Unblocked))), "{:?}", calls);
+        assert!(calls.iter().any(|call| call.starts_with("b.txt")), "{:?}", calls);
+    }
+
+    #[test]
+    fn test_observer_sees_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let blocked = temp_dir.path().join("a.zip");
+        let journal = temp_dir.path().join("journal.jsonl").to_string_lossy().to_string();
+        fs::write(&blocked, b"zip").unwrap();
+
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&blocked, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.backend = backend;
+        config.journal_path = Some(journal.clone());
+        process_target(&blocked.to_string_lossy(), &mut config).unwrap();
+
+        let recorder = Arc::new(Recorder::default());
+        config.observer = Some(recorder.clone());
+        let filter = JournalFilter { path_prefix: None, since: None, until: None };
+        restore_journal(&journal, &filter, &mut config).unwrap();
+
+        let calls = recorder.calls.lock().unwrap();
+        let expected = vec![
+            "started Some(1)".to_string(),
+            format!("a.zip {:?}", Some(FileOutcome::Restored)),
+            "finished 1".to_string(),
+        ];
+        assert_eq!(*calls, expected);
+    }
+}
@@ -39,34 +39,49 @@ pub fn validate_path(path: &str) -> Result<PathBuf> {
     Ok(path.to_path_buf())
 }
 
+/// Name of the alternate data stream Windows uses for the Mark of the Web
+pub const ZONE_IDENTIFIER_STREAM: &str = "Zone.Identifier";
+
 /// Generate the ADS (Alternate Data Stream) path for Zone.Identifier
 pub fn get_ads_path(file_path: &Path) -> Result<PathBuf> {
+    get_stream_path(file_path, ZONE_IDENTIFIER_STREAM)
+}
+
+/// Generate the path of an arbitrary named stream on a file
+pub fn get_stream_path(file_path: &Path, stream: &str) -> Result<PathBuf> {
     let file_path = validate_path(&file_path.to_string_lossy())?;
     let mut ads_path = file_path.as_os_str().to_os_string();
-    ads_path.push(":Zone.Identifier");
+    ads_path.push(":");
+    ads_path.push(stream);
     Ok(PathBuf::from(ads_path))
 }
 
+/// System directories that are never processed unless the configuration replaces the list
+pub const DEFAULT_PROTECTED_PATHS: &[&str] = &[
+    "C:\\Windows\\System32",
+    "C:\\Windows\\SysWOW64", 
+    "C:\\Program Files\\Windows",
+    "\\\\?\\",  // Raw device paths
+];
+
+/// The default protected path list as owned strings, for use in a configuration
+pub fn default_protected_paths() -> Vec<String> {
+    DEFAULT_PROTECTED_PATHS.iter().map(|prefix| prefix.to_string()).collect()
+}
+
 /// Check if a path is safe to process (additional security checks)
 pub fn is_safe_path(path: &Path) -> bool {
+    is_outside_protected(path, &default_protected_paths())
+}
+
+/// Check that a path does not start with any of the given protected prefixes
+///
+/// Callers report a protected path themselves, so it is only warned about once.
+pub fn is_outside_protected(path: &Path, protected_paths: &[String]) -> bool {
     let path_str = path.to_string_lossy();
     
     // Don't process system directories
-    let dangerous_prefixes = [
-        "C:\\Windows\\System32",
-        "C:\\Windows\\SysWOW64", 
-        "C:\\Program Files\\Windows",
-        "\\\\?\\",  // Raw device paths
-    ];
-    
-    for prefix in &dangerous_prefixes {
-        if path_str.starts_with(prefix) {
-            log::warn!("Skipping potentially dangerous system path: {}", path_str);
-            return false;
-        }
-    }
-    
-    true
+    !protected_paths.iter().any(|prefix| path_str.starts_with(prefix.as_str()))
 }
 
 #[cfg(test)]
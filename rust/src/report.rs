@@ -0,0 +1,217 @@
+//! Machine-readable JSON report of a run.
+
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use crate::targets::TargetStats;
+use crate::unblocker::{FileOutcome, FileRecord, SkipReason, UnblockStats};
+use crate::{APP_NAME, APP_VERSION};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Version of the report layout; bumped whenever a field changes meaning or is removed
+pub const REPORT_VERSION: u32 = 1;
+
+/// The complete result of a run, written by `--format json`
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub version: u32,
+    pub tool: &'static str,
+    pub tool_version: &'static str,
+    pub finished_at: DateTime<Utc>,
+    /// Operation that was run: `unblock`, `scan`, `block`, `restore` or `watch`
+    pub mode: String,
+    pub options: ReportOptions,
+    /// Totals over every target
+    pub stats: UnblockStats,
+    pub targets: Vec<TargetReport>,
+    /// Every file in traversal order, across all targets
+    pub files: Vec<FileReport>,
+}
+
+/// Options that shaped the run
+#[derive(Debug, Serialize)]
+pub struct ReportOptions {
+    pub dry_run: bool,
+    pub zones: Option<Vec<u32>>,
+    pub downgrade_to: Option<u32>,
+    pub journal: Option<String>,
+    pub allow_risky: bool,
+    pub risky_extensions: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub trusted_origins: Vec<String>,
+    pub denied_origins: Vec<String>,
+    pub hash_manifest: Option<String>,
+    pub verify_checksums: bool,
+    pub scan_command: Option<String>,
+    pub workers: usize,
+}
+
+/// Statistics for one target
+#[derive(Debug, Serialize)]
+pub struct TargetReport {
+    pub path: String,
+    pub stats: UnblockStats,
+}
+
+/// What happened to one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    /// `unblocked`, `no_ads`, `detected`, `blocked`, `downgraded`, `restored`, `skipped` or `failed`
+    pub outcome: &'static str,
+    /// Why a file was skipped, e.g. `zone` or `high_risk`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    /// Zone of a skipped or detected file, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referrer_url: Option<String>,
+    /// Error kind of a failed file, see `UnblockerError::kind`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&FileRecord> for FileReport {
+    fn from(record: &FileRecord) -> Self {
+        let mut report = FileReport {
+            path: record.path.display().to_string(),
+            outcome: "failed",
+            reason: None,
+            zone: None,
+            host_url: None,
+            referrer_url: None,
+            error_kind: None,
+            error: None,
+        };
+
+        match &record.outcome {
+            Ok(FileOutcome::Unblocked) => report.outcome = "unblocked",
+            Ok(FileOutcome::NoAds) => report.outcome = "no_ads",
+            Ok(FileOutcome::Blocked) => report.outcome = "blocked",
+            Ok(FileOutcome::Downgraded) => report.outcome = "downgraded",
+            Ok(FileOutcome::Restored) => report.outcome = "restored",
+            Ok(FileOutcome::Detected(zone_info)) => {
+                report.outcome = "detected";
+                if let Some(info) = zone_info {
+                    report.zone = info.zone_id.map(|zone| zone.id());
+                    report.host_url = info.host_url.clone();
+                    report.referrer_url = info.referrer_url.clone();
+                }
+            }
+            Ok(FileOutcome::Skipped(reason)) => {
+                report.outcome = "skipped";
+                report.reason = Some(reason.kind());
+                if let SkipReason::Zone(zone) = reason {
+                    report.zone = Some(zone.id());
+                }
+            }
+            Err(failure) => {
+                report.error_kind = Some(failure.kind);
+                report.error = Some(failure.message.clone());
+            }
+        }
+
+        report
+    }
+}
+
+impl RunReport {
+    /// Build the report for a finished run
+    pub fn new(mode: &str, result: &TargetStats, config: &Config) -> Self {
+        Self {
+            version: REPORT_VERSION,
+            tool: APP_NAME,
+            tool_version: APP_VERSION,
+            finished_at: Utc::now(),
+            mode: mode.to_string(),
+            options: ReportOptions {
+                dry_run: config.dry_run,
+                zones: config.unblock_zones.as_ref().map(|zones| zones.iter().map(|zone| zone.id()).collect()),
+                downgrade_to: config.downgrade_to.map(|zone| zone.id()),
+                journal: config.journal_path.clone(),
+                allow_risky: config.allow_risky,
+                risky_extensions: config.risky_extensions.clone(),
+                include: config.include.clone(),
+                exclude: config.exclude.clone(),
+                trusted_origins: config.trusted_origins.clone(),
+                denied_origins: config.denied_origins.clone(),
+                hash_manifest: config.hash_manifest.as_ref().map(|manifest| manifest.path.display().to_string()),
+                verify_checksums: config.verify_checksums,
+                scan_command: config.scan_command.clone(),
+                workers: config.effective_workers(),
+            },
+            stats: counts(&result.total),
+            targets: result.roots.iter()
+                .map(|root| TargetReport { path: root.root.clone(), stats: counts(&root.stats) })
+                .collect(),
+            files: result.total.records.iter().map(FileReport::from).collect(),
+        }
+    }
+
+    /// Serialize the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| UnblockerError::Config(format!("Cannot serialize report: {}", e)))
+    }
+}
+
+/// Copy the counters of a run without its per-file records
+fn counts(stats: &UnblockStats) -> UnblockStats {
+    let mut counts = UnblockStats::default();
+    counts.merge(stats);
+    counts.records.clear();
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UnblockerError;
+    use crate::targets::RootStats;
+    use crate::unblocker::FileFailure;
+    use crate::zone::Zone;
+
+    #[test]
+    fn test_report_json() {
+        let records = vec![
+            FileRecord { path: "a.zip".into(), outcome: Ok(FileOutcome::Unblocked) },
+            FileRecord { path: "b.zip".into(), outcome: Ok(FileOutcome::Skipped(SkipReason::Zone(Zone::Restricted))) },
+            FileRecord {
+                path: "c.zip".into(),
+                outcome: Err(FileFailure::from(&UnblockerError::PermissionDenied("c.zip".to_string()))),
+            },
+        ];
+        let stats = UnblockStats {
+            files_processed: 3,
+            files_unblocked: 1,
+            files_restricted: 1,
+            files_failed: 1,
+            permission_errors: 1,
+            records,
+            ..Default::default()
+        };
+        let mut result = TargetStats::default();
+        result.total.merge(&stats);
+        result.roots.push(RootStats { root: "C:\\Downloads".to_string(), stats });
+
+        let report = RunReport::new("unblock", &result, &Config::default());
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["version"], REPORT_VERSION);
+        assert_eq!(json["mode"], "unblock");
+        assert_eq!(json["stats"]["files_unblocked"], 1);
+        assert_eq!(json["targets"][0]["path"], "C:\\Downloads");
+        assert!(json["stats"].get("records").is_none());
+        assert_eq!(json["files"][0]["outcome"], "unblocked");
+        assert_eq!(json["files"][1]["reason"], "zone");
+        assert_eq!(json["files"][1]["zone"], 4);
+        assert_eq!(json["files"][2]["outcome"], "failed");
+        assert_eq!(json["files"][2]["error_kind"], "permission_denied");
+    }
+}
@@ -0,0 +1,62 @@
+//! Classification of file types that are dangerous to unblock.
+
+use std::path::Path;
+
+/// Extensions treated as high-risk unless the configuration replaces the list
+///
+/// Covers executables, installers, scripts, shortcuts and macro-enabled Office documents.
+pub const DEFAULT_RISKY_EXTENSIONS: &[&str] = &[
+    "exe", "com", "scr", "pif", "cpl", "msi", "msp", "msix", "appx", "appinstaller",
+    "bat", "cmd", "ps1", "psm1", "vbs", "vbe", "js", "jse", "wsf", "wsh", "hta",
+    "jar", "lnk", "url", "reg", "chm", "iso", "img", "vhd", "vhdx",
+    "docm", "dotm", "xlsm", "xltm", "xlam", "pptm", "potm", "ppam", "ppsm", "sldm",
+];
+
+/// How dangerous it is to lift the Mark of the Web from a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskLevel {
+    Normal,
+    /// The file can run code or open content that runs code
+    High,
+}
+
+/// The default risky extension list as owned strings, for use in a configuration
+pub fn default_risky_extensions() -> Vec<String> {
+    DEFAULT_RISKY_EXTENSIONS.iter().map(|ext| ext.to_string()).collect()
+}
+
+/// Classify a file by its extension, ignoring case and any leading dot in the list
+pub fn classify(path: &Path, risky_extensions: &[String]) -> RiskLevel {
+    let extension = match path.extension() {
+        Some(extension) => extension.to_string_lossy(),
+        None => return RiskLevel::Normal,
+    };
+
+    let risky = risky_extensions
+        .iter()
+        .any(|risky| risky.trim_start_matches('.').eq_ignore_ascii_case(&extension));
+
+    if risky {
+        RiskLevel::High
+    } else {
+        RiskLevel::Normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let defaults = default_risky_extensions();
+        assert_eq!(classify(Path::new("setup.EXE"), &defaults), RiskLevel::High);
+        assert_eq!(classify(Path::new("report.xlsm"), &defaults), RiskLevel::High);
+        assert_eq!(classify(Path::new("report.xlsx"), &defaults), RiskLevel::Normal);
+        assert_eq!(classify(Path::new("README"), &defaults), RiskLevel::Normal);
+
+        let custom = vec![".PDF".to_string()];
+        assert_eq!(classify(Path::new("paper.pdf"), &custom), RiskLevel::High);
+        assert_eq!(classify(Path::new("setup.exe"), &custom), RiskLevel::Normal);
+    }
+}
@@ -0,0 +1,250 @@
+//! Size- and age-based rotation of the log file.
+
+use crate::error::{Result, UnblockerError};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Rotated files kept by default
+pub const DEFAULT_KEEP: usize = 5;
+
+/// A rotation lock older than this was left behind by a process that died
+const STALE_LOCK: Duration = Duration::from_secs(30);
+
+/// When the log file is rotated and how many old files are kept
+///
+/// Rotated files are named `<log>.1`, `<log>.2`, ... from newest to oldest,
+/// with `.gz` appended when they are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRotation {
+    /// Rotate before the file would grow beyond this many bytes
+    pub max_size: Option<u64>,
+    /// Rotate once the file was created this long ago
+    pub max_age: Option<Duration>,
+    /// Number of rotated files kept; older ones are deleted
+    pub keep: usize,
+    /// Compress rotated files with gzip
+    pub compress: bool,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            max_age: None,
+            keep: DEFAULT_KEEP,
+            compress: false,
+        }
+    }
+}
+
+impl LogRotation {
+    /// Check whether any rotation limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.max_age.is_some()
+    }
+
+    /// Check whether a log file must be rotated before `incoming` more bytes are written
+    ///
+    /// An empty file is never rotated.
+    pub fn is_due(&self, size: u64, incoming: u64, created: Option<SystemTime>) -> bool {
+        let too_big = self.max_size.is_some_and(|max| size + incoming > max);
+        let too_old = self.max_age.zip(created)
+            .is_some_and(|(max, created)| created.elapsed().unwrap_or_default() >= max);
+        size > 0 && (too_big || too_old)
+    }
+}
+
+/// Parse a size such as `10MB`, `512K` or `1048576`
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => 0,
+    };
+
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|size| *size > 0)
+        .ok_or_else(|| UnblockerError::Config(format!("Invalid size '{}': expected e.g. 10MB, 512KB or a number of bytes", value)))
+}
+
+/// Parse a duration such as `7d`, `12h`, `30m` or `90s`
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let seconds: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 0,
+    };
+
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(seconds))
+        .filter(|age| *age > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| UnblockerError::Config(format!("Invalid duration '{}': expected e.g. 7d, 12h, 30m or 90s", value)))
+}
+
+/// Name of the `n`th rotated file
+fn rotated_name(path: &Path, n: usize, compressed: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}{}", n, if compressed { ".gz" } else { "" }));
+    PathBuf::from(name)
+}
+
+/// Exclusive right to rotate a log file, held by creating `<log>.lock`
+struct RotationLock {
+    path: PathBuf,
+}
+
+impl RotationLock {
+    /// Take the lock, or return `None` when another process is rotating the file
+    fn acquire(log_path: &Path) -> io::Result<Option<Self>> {
+        let mut name = log_path.as_os_str().to_owned();
+        name.push(".lock");
+        let path = PathBuf::from(name);
+
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Some(Self { path })),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > STALE_LOCK);
+                    if !stale {
+                        return Ok(None);
+                    }
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for RotationLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Replace a rotated file with its gzip-compressed copy
+fn compress(path: &Path) -> io::Result<()> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+
+    let mut input = BufReader::new(File::open(path)?);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(PathBuf::from(name))?), Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.flush()?;
+
+    fs::remove_file(path)
+}
+
+/// Rotate the log file if it is still due once the rotation lock is held
+///
+/// Returns whether the file was rotated. When another process is rotating at the
+/// same moment, or has just rotated, nothing happens and the caller simply
+/// reopens the log path.
+pub fn rotate(path: &Path, rotation: &LogRotation, incoming: u64) -> io::Result<bool> {
+    let Some(_lock) = RotationLock::acquire(path)? else {
+        return Ok(false);
+    };
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if !rotation.is_due(metadata.len(), incoming, metadata.created().ok()) {
+        return Ok(false);
+    }
+
+    // Drop the oldest file and shift the others up by one
+    for n in (1..=rotation.keep).rev() {
+        for compressed in [false, true] {
+            let from = rotated_name(path, n, compressed);
+            if !from.exists() {
+                continue;
+            }
+            if n == rotation.keep {
+                fs::remove_file(&from)?;
+            } else {
+                fs::rename(&from, rotated_name(path, n + 1, compressed))?;
+            }
+        }
+    }
+
+    if rotation.keep == 0 {
+        fs::remove_file(path)?;
+    } else {
+        let first = rotated_name(path, 1, false);
+        fs::rename(path, &first)?;
+        if rotation.compress {
+            compress(&first)?;
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_rotate_keeps_and_compresses() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("run.log");
+        let rotation = LogRotation { max_size: Some(10), keep: 2, compress: true, ..Default::default() };
+
+        for run in ["first run\n", "second run\n", "third run\n"] {
+            fs::write(&log, run).unwrap();
+            assert!(rotate(&log, &rotation, 1).unwrap());
+        }
+        assert!(!log.exists());
+        assert!(!rotated_name(&log, 3, true).exists());
+
+        let mut newest = String::new();
+        GzDecoder::new(File::open(rotated_name(&log, 1, true)).unwrap()).read_to_string(&mut newest).unwrap();
+        assert_eq!(newest, "third run\n");
+        assert!(rotated_name(&log, 2, true).exists());
+
+        // Small files, and files another process is rotating, are left alone
+        fs::write(&log, "x").unwrap();
+        assert!(!rotate(&log, &rotation, 1).unwrap());
+        fs::write(&log, "long enough to rotate\n").unwrap();
+        let lock = RotationLock::acquire(&log).unwrap().unwrap();
+        assert!(!rotate(&log, &rotation, 1).unwrap());
+        drop(lock);
+        assert!(rotate(&log, &rotation, 1).unwrap());
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_size("10MB").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("512 k").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("10 parsecs").is_err());
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 24 * 3600));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert!(parse_duration("7").is_err());
+    }
+}
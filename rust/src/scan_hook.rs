@@ -0,0 +1,418 @@
+//! External scanner command run on each file before it is unblocked.
+
+use crate::error::{Result, UnblockerError};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Placeholder in the scanner command replaced by the file path
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Default time a scanner may take for one file
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Longest scanner output kept for the log
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+/// How long output may still arrive once the scanner exited or was killed
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Why the scanner did not clear a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// The scanner exited with a non-zero code, or was killed by a signal
+    Rejected(Option<i32>),
+    /// The scanner did not finish within the timeout and was killed
+    TimedOut,
+    /// The scanner could not be started
+    Failed,
+}
+
+/// Result of running the scanner on one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOutcome {
+    /// `None` when the scanner exited with code 0
+    pub verdict: Option<ScanVerdict>,
+    /// Combined standard output and error, trimmed and shortened for the log
+    pub output: String,
+}
+
+/// Limits how many scanner processes run at once across worker threads
+///
+/// Shared by every clone of a configuration.
+#[derive(Debug, Default)]
+pub struct ScanSlots {
+    in_use: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// A claimed scanner slot, released when dropped
+struct SlotGuard<'a> {
+    slots: &'a ScanSlots,
+}
+
+impl ScanSlots {
+    fn acquire(&self, limit: usize) -> SlotGuard<'_> {
+        let mut in_use = self.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_use >= limit.max(1) {
+            in_use = self.freed.wait(in_use).unwrap_or_else(|e| e.into_inner());
+        }
+        *in_use += 1;
+        SlotGuard { slots: self }
+    }
+}
+
+impl Drop for SlotGuard<'_> {
+    fn drop(&mut self) {
+        let mut in_use = self.slots.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        *in_use -= 1;
+        self.slots.freed.notify_one();
+    }
+}
+
+/// Split a command line into program and arguments
+///
+/// Arguments are separated by whitespace; double quotes group an argument
+/// containing spaces, such as `"C:\Program Files\Scanner\scan.exe"`.
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_part = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_part = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_part {
+                    parts.push(std::mem::take(&mut current));
+                    in_part = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_part = true;
+            }
+        }
+    }
+
+    if quoted {
+        return Err(UnblockerError::Config("scan_command: unmatched double quote".to_string()));
+    }
+    if in_part {
+        parts.push(current);
+    }
+    if parts.is_empty() {
+        return Err(UnblockerError::Config("scan_command: command is empty".to_string()));
+    }
+
+    Ok(parts)
+}
+
+/// Build the scanner process for a file
+///
+/// The path replaces every `{file}` placeholder, or is appended when there is none.
+fn build_command(parts: &[String], file_path: &Path) -> Command {
+    let path = file_path.to_string_lossy();
+    let mut args: Vec<String> = parts[1..].iter().map(|arg| arg.replace(FILE_PLACEHOLDER, &path)).collect();
+    if !parts[1..].iter().any(|arg| arg.contains(FILE_PLACEHOLDER)) {
+        args.push(path.to_string());
+    }
+
+    let mut command = Command::new(&parts[0]);
+    command.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW: keep console scanners from flashing a window
+        command.creation_flags(0x0800_0000);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // A group of its own lets a timeout stop the processes the scanner started as well
+        command.process_group(0);
+    }
+
+    command
+}
+
+/// The scanner and every process it starts, so that they can be stopped together
+///
+/// On Windows the scanner runs in a job object that kills whatever is left of
+/// it when the job is closed, even if this process dies first.
+#[cfg(windows)]
+struct ProcessTree(Option<windows::Win32::Foundation::HANDLE>);
+
+#[cfg(windows)]
+impl ProcessTree {
+    fn new(child: &Child) -> Self {
+        use std::os::windows::io::AsRawHandle;
+        use windows::core::PCWSTR;
+        use windows::Win32::Foundation::{CloseHandle, HANDLE};
+        use windows::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        unsafe {
+            let job = match CreateJobObjectW(None, PCWSTR::null()) {
+                Ok(job) => job,
+                Err(e) => {
+                    log::debug!("Scanner runs without a job object: {}", e);
+                    return Self(None);
+                }
+            };
+
+            let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let assigned = SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const std::ffi::c_void,
+                std::mem::size_of_val(&limits) as u32,
+            )
+            .and_then(|_| AssignProcessToJobObject(job, HANDLE(child.as_raw_handle())));
+
+            if let Err(e) = assigned {
+                log::debug!("Scanner runs without a job object: {}", e);
+                let _ = CloseHandle(job);
+                return Self(None);
+            }
+            Self(Some(job))
+        }
+    }
+
+    fn kill(&mut self) {
+        use windows::Win32::Foundation::CloseHandle;
+        use windows::Win32::System::JobObjects::TerminateJobObject;
+
+        if let Some(job) = self.0.take() {
+            unsafe {
+                let _ = TerminateJobObject(job, 1);
+                let _ = CloseHandle(job);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// The scanner and every process it starts, so that they can be stopped together
+///
+/// The scanner leads a process group of its own, which is killed as a whole.
+#[cfg(unix)]
+struct ProcessTree(Option<u32>);
+
+#[cfg(unix)]
+impl ProcessTree {
+    fn new(child: &Child) -> Self {
+        Self(Some(child.id()))
+    }
+
+    fn kill(&mut self) {
+        if let Some(group) = self.0.take() {
+            unsafe {
+                libc::kill(-(group as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Collect a child's output on a separate thread so a chatty scanner cannot block on a full pipe
+///
+/// The output is sent tagged with `index` once the pipe is closed.
+fn drain(pipe: Option<impl Read + Send + 'static>, index: usize, tx: Sender<(usize, String)>) {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        let _ = tx.send((index, String::from_utf8_lossy(&output).into_owned()));
+    });
+}
+
+/// Wait for the child until the deadline, killing it and its processes if it runs over
+fn wait_with_timeout(child: &mut Child, tree: &mut ProcessTree, timeout: Duration) -> std::io::Result<Option<ScanVerdict>> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(if status.success() { None } else { Some(ScanVerdict::Rejected(status.code())) });
+        }
+        if Instant::now() >= deadline {
+            tree.kill();
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(Some(ScanVerdict::TimedOut));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Standard output followed by standard error of a scanner that exited
+///
+/// Processes the scanner left running may keep a pipe open. They are killed
+/// once the grace period is over, and a pipe that stays open even then is
+/// given up on rather than waited for.
+fn collect_output(rx: &Receiver<(usize, String)>, tree: &mut ProcessTree) -> String {
+    let mut parts = [String::new(), String::new()];
+    let mut pending = parts.len();
+    let mut deadline = Instant::now() + OUTPUT_GRACE;
+    let mut killed = false;
+
+    while pending > 0 {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((index, output)) => {
+                parts[index] = output;
+                pending -= 1;
+            }
+            Err(RecvTimeoutError::Timeout) if !killed => {
+                tree.kill();
+                killed = true;
+                deadline = Instant::now() + OUTPUT_GRACE;
+            }
+            Err(_) => break,
+        }
+    }
+
+    parts.concat()
+}
+
+/// Run the scanner command on a file, waiting for a free slot first
+pub fn run_scan(parts: &[String], file_path: &Path, timeout: Duration, slots: &ScanSlots, concurrency: usize) -> ScanOutcome {
+    let _slot = slots.acquire(concurrency);
+
+    let mut child = match build_command(parts, file_path).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return ScanOutcome {
+                verdict: Some(ScanVerdict::Failed),
+                output: format!("cannot start {}: {}", parts[0], e),
+            };
+        }
+    };
+
+    let mut tree = ProcessTree::new(&child);
+    let (tx, rx) = mpsc::channel();
+    drain(child.stdout.take(), 0, tx.clone());
+    drain(child.stderr.take(), 1, tx);
+    let verdict = wait_with_timeout(&mut child, &mut tree, timeout).unwrap_or(Some(ScanVerdict::Failed));
+    if verdict == Some(ScanVerdict::TimedOut) {
+        // The readers end on their own now that every process holding the pipes is gone
+        return ScanOutcome {
+            verdict,
+            output: format!("no verdict within {} seconds", timeout.as_secs_f32()),
+        };
+    }
+
+    let output = collect_output(&rx, &mut tree);
+    let output = output.trim();
+    let output = match output.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((end, _)) => format!("{}...", &output[..end]),
+        None => output.to_string(),
+    };
+
+    ScanOutcome { verdict, output }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        let parts = split_command(r#""C:\Program Files\Scanner\scan.exe" --quiet  {file}"#).unwrap();
+        assert_eq!(parts, vec![r"C:\Program Files\Scanner\scan.exe", "--quiet", "{file}"]);
+        assert_eq!(split_command(r#"scan "" x"#).unwrap(), vec!["scan", "", "x"]);
+        assert!(matches!(split_command("scan \"unterminated"), Err(UnblockerError::Config(_))));
+        assert!(matches!(split_command("   "), Err(UnblockerError::Config(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_scan_verdicts() {
+        let slots = ScanSlots::default();
+        let timeout = Duration::from_secs(5);
+        let sh = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string(), "scanner".to_string()];
+
+        let clean = run_scan(&sh("echo \"OK $1\""), Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(clean, ScanOutcome { verdict: None, output: "OK a.zip".to_string() });
+
+        let rejected = run_scan(&sh("echo FOUND >&2; exit 1"), Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(rejected.verdict, Some(ScanVerdict::Rejected(Some(1))));
+        assert_eq!(rejected.output, "FOUND");
+
+        let slow = run_scan(&sh("sleep 5"), Path::new("a.zip"), Duration::from_millis(100), &slots, 1);
+        assert_eq!(slow.verdict, Some(ScanVerdict::TimedOut));
+
+        let missing = run_scan(&["/nonexistent/scanner".to_string()], Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(missing.verdict, Some(ScanVerdict::Failed));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_scan_stops_leftover_processes() {
+        let slots = ScanSlots::default();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let pid_file = temp_dir.path().join("helper.pid");
+        let script = format!("sleep 30 & echo $! > '{}'; echo started", pid_file.display());
+        let parts = vec!["sh".to_string(), "-c".to_string(), script];
+
+        // The helper keeps standard output open after the scanner exited
+        let start = Instant::now();
+        let outcome = run_scan(&parts, Path::new("a.zip"), Duration::from_secs(5), &slots, 1);
+        assert_eq!(outcome.verdict, None);
+        assert!(start.elapsed() < Duration::from_secs(5), "{:?}", start.elapsed());
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let alive = || Command::new("kill").args(["-0", pid.trim()]).stderr(Stdio::null()).status().unwrap().success();
+        let gone_by = Instant::now() + Duration::from_secs(2);
+        while alive() && Instant::now() < gone_by {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!alive(), "helper {} still running", pid.trim());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_run_scan_verdicts() {
+        let slots = ScanSlots::default();
+        let timeout = Duration::from_secs(5);
+        let cmd = |args: &[&str]| {
+            let mut parts = vec!["cmd".to_string(), "/c".to_string()];
+            parts.extend(args.iter().map(|arg| arg.to_string()));
+            parts
+        };
+
+        let clean = run_scan(&cmd(&["echo", "OK"]), Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(clean, ScanOutcome { verdict: None, output: "OK a.zip".to_string() });
+
+        let rejected = run_scan(&cmd(&["echo", "FOUND", "1>&2", "&", "exit", "1", "&", "rem", "{file}"]), Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(rejected.verdict, Some(ScanVerdict::Rejected(Some(1))));
+        assert_eq!(rejected.output, "FOUND");
+
+        let slow = run_scan(&cmd(&["ping", "-n", "6", "127.0.0.1", ">nul", "&", "rem"]), Path::new("a.zip"), Duration::from_millis(100), &slots, 1);
+        assert_eq!(slow.verdict, Some(ScanVerdict::TimedOut));
+
+        let missing = run_scan(&[r"C:\nonexistent\scanner.exe".to_string()], Path::new("a.zip"), timeout, &slots, 1);
+        assert_eq!(missing.verdict, Some(ScanVerdict::Failed));
+    }
+}
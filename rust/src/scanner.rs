@@ -0,0 +1,184 @@
+//! Read-only inspection of Zone.Identifier streams.
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::ui::log_message;
+use crate::unblocker::{prepare_file, read_zone_stream, FileOutcome, SkipReason, UnblockStats};
+use crate::walker::walk_target;
+use crate::zone::ZoneInfo;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A file found to carry a Zone.Identifier stream
+#[derive(Debug, Clone)]
+pub struct ScanEntry {
+    /// Path of the blocked file
+    pub path: PathBuf,
+    /// Parsed stream contents, or `None` if the stream could not be parsed
+    pub zone_info: Option<ZoneInfo>,
+}
+
+impl ScanEntry {
+    /// Describe the entry on a single line
+    pub fn describe(&self) -> String {
+        match &self.zone_info {
+            Some(info) => {
+                let zone = info.zone_id
+                    .map(|zone| zone.to_string())
+                    .unwrap_or_else(|| "none".to_string());
+                format!(
+                    "{} [ZoneId={}, HostUrl={}, ReferrerUrl={}]",
+                    self.path.display(),
+                    zone,
+                    info.host_url.as_deref().unwrap_or("-"),
+                    info.referrer_url.as_deref().unwrap_or("-"),
+                )
+            }
+            None => format!("{} [unreadable Zone.Identifier]", self.path.display()),
+        }
+    }
+}
+
+/// Result of a read-only scan
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub stats: UnblockStats,
+    /// Every file that still has a Zone.Identifier stream, sorted by path
+    pub entries: Vec<ScanEntry>,
+}
+
+impl ScanReport {
+    /// Create a summary message for the scan
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Scanned {} files: {} blocked, {} had no ADS, {} skipped, {} failed ({} permission errors)",
+            self.stats.files_processed,
+            self.stats.files_detected,
+            self.stats.files_no_ads,
+            self.stats.files_skipped,
+            self.stats.files_failed,
+            self.stats.permission_errors
+        );
+
+        if self.stats.files_filtered > 0 {
+            summary.push_str(&format!(", {} filtered out", self.stats.files_filtered));
+        }
+
+        summary
+    }
+}
+
+/// Inspect a single file without modifying it
+pub fn scan_file(file_path: &str, config: &mut Config) -> Result<Option<ScanEntry>> {
+    match prepare_file(file_path, config)? {
+        Some(file_path) => inspect(file_path, config),
+        None => Ok(None),
+    }
+}
+
+/// Walk a file or directory and report every file that has a Zone.Identifier stream
+///
+/// Nothing is ever removed or rewritten.
+pub fn scan_target(target_path: &str, config: &mut Config) -> Result<ScanReport> {
+    // Only detected files are kept, so large clean trees do not hold a record per file
+    let detected = Mutex::new(Vec::new());
+    let collect_records = config.collect_records;
+    let stats = walk_target(target_path, config, collect_records, |path, config| {
+        let file_path = match prepare_file(path, config)? {
+            Some(file_path) => file_path,
+            None => return Ok(FileOutcome::Skipped(SkipReason::UnsafePath)),
+        };
+
+        match inspect(file_path, config)? {
+            Some(entry) => {
+                let zone_info = entry.zone_info.clone();
+                detected.lock().unwrap_or_else(|e| e.into_inner()).push(entry);
+                Ok(FileOutcome::Detected(zone_info))
+            }
+            None => Ok(FileOutcome::NoAds),
+        }
+    })?;
+
+    // Worker threads finish files in any order
+    let mut entries = detected.into_inner().unwrap_or_else(|e| e.into_inner());
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(ScanReport { stats, entries })
+}
+
+fn inspect(file_path: PathBuf, config: &mut Config) -> Result<Option<ScanEntry>> {
+    let data = match read_zone_stream(&file_path, config)? {
+        Some(data) => data,
+        None => {
+            log_message(&format!("No ADS found: {}", file_path.display()), config)?;
+            return Ok(None);
+        }
+    };
+
+    let zone_info = match ZoneInfo::parse(&data) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            log_message(&format!("Could not parse Zone.Identifier of {}: {}", file_path.display(), e), config)?;
+            None
+        }
+    };
+
+    let entry = ScanEntry { path: file_path, zone_info };
+    log_message(&format!("Zone.Identifier found: {}", entry.describe()), config)?;
+    Ok(Some(entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+    use crate::zone::Zone;
+    use std::fs::File;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_target_reports_without_modifying() {
+        let temp_dir = tempdir().unwrap();
+        let blocked = temp_dir.path().join("setup.exe");
+        let broken = temp_dir.path().join("broken.txt");
+        let clean = temp_dir.path().join("clean.txt");
+        for path in [&blocked, &broken, &clean] {
+            File::create(path).unwrap();
+        }
+
+        let stream = b"[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://example.com/\r\nHostUrl=https://example.com/setup.exe\r\n";
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&blocked, ZONE_IDENTIFIER_STREAM, stream).unwrap();
+        backend.write_stream(&broken, ZONE_IDENTIFIER_STREAM, b"garbage").unwrap();
+
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+
+        let report = scan_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(report.stats.files_processed, 3);
+        assert_eq!(report.stats.files_detected, 2);
+        assert_eq!(report.stats.files_no_ads, 1);
+        assert_eq!(report.stats.files_unblocked, 0);
+        assert_eq!(report.entries.len(), 2);
+        assert!(report.stats.records.is_empty());
+
+        let entry = report.entries.iter().find(|e| e.path == blocked).unwrap();
+        let info = entry.zone_info.as_ref().unwrap();
+        assert_eq!(info.zone_id, Some(Zone::Internet));
+        assert_eq!(info.host_url.as_deref(), Some("https://example.com/setup.exe"));
+        assert!(entry.describe().contains("ZoneId=3 (Internet)"));
+
+        let entry = report.entries.iter().find(|e| e.path == broken).unwrap();
+        assert!(entry.zone_info.is_none());
+
+        // The scan must leave every stream in place
+        assert_eq!(backend.read_stream(&blocked, ZONE_IDENTIFIER_STREAM).unwrap(), stream);
+        assert!(backend.has_stream(&broken, ZONE_IDENTIFIER_STREAM));
+    }
+}
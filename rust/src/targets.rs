@@ -0,0 +1,269 @@
+//! Collection of target paths from the command line, file lists and response files.
+
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use crate::events::{emit, Event};
+use crate::report::FileReport;
+use crate::ui::log_message;
+use crate::unblocker::{FileFailure, FileRecord, UnblockStats};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Statistics for one target root
+#[derive(Debug)]
+pub struct RootStats {
+    pub root: String,
+    pub stats: UnblockStats,
+}
+
+/// Statistics for every target root and their total
+#[derive(Debug, Default)]
+pub struct TargetStats {
+    /// Per-root statistics, in the order the roots were given
+    pub roots: Vec<RootStats>,
+    pub total: UnblockStats,
+}
+
+/// Replace every `@file` argument with the arguments listed in that file, one per line
+///
+/// Blank lines and lines starting with `#` are ignored. The first argument is the
+/// program name and is never expanded.
+pub fn expand_response_files<I>(args: I) -> Result<Vec<String>>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut expanded = Vec::new();
+
+    for (index, arg) in args.into_iter().enumerate() {
+        match arg.strip_prefix('@') {
+            Some(response_file) if index > 0 && !response_file.is_empty() => {
+                let contents = fs::read_to_string(response_file).map_err(|e| {
+                    UnblockerError::Config(format!("Cannot read response file {}: {}", response_file, e))
+                })?;
+
+                expanded.extend(
+                    contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(|line| line.trim_matches('"').to_string()),
+                );
+            }
+            _ => expanded.push(arg),
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Read a list of paths from a file, or from standard input when `source` is `-`
+///
+/// The list is NUL-separated if it contains a NUL byte, otherwise one path per line.
+pub fn read_path_list(source: &str) -> Result<Vec<String>> {
+    let mut contents = String::new();
+
+    if source == "-" {
+        io::stdin().read_to_string(&mut contents)?;
+    } else {
+        contents = fs::read_to_string(source).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => UnblockerError::PathNotFound(source.to_string()),
+            _ => UnblockerError::Io(e),
+        })?;
+    }
+
+    let separator = if contents.contains('\0') { '\0' } else { '\n' };
+
+    Ok(contents
+        .split(separator)
+        .map(|path| path.trim_end_matches('\r'))
+        .filter(|path| !path.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Write a NUL-separated path list to a new file in `dir`, for `--files-from`
+///
+/// Returns the path of the file, which the caller removes once it was read.
+pub fn write_path_list(paths: &[String], dir: &Path) -> Result<PathBuf> {
+    let contents: String = paths.iter().map(|path| format!("{}\0", path)).collect();
+
+    let mut attempt = 0;
+    loop {
+        let path = dir.join(format!("unblocker-{}-{}.lst", std::process::id(), attempt));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(UnblockerError::Io(e)),
+        }
+    }
+}
+
+/// Remove duplicate roots and roots that lie inside another root
+///
+/// The first occurrence of each remaining root keeps its position.
+pub fn dedupe_roots(roots: Vec<String>) -> Vec<String> {
+    let keys: Vec<PathBuf> = roots.iter().map(|root| root_key(root)).collect();
+
+    roots
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            let key = &keys[*index];
+            !keys.iter().enumerate().any(|(other, other_key)| {
+                other != *index
+                    && key.starts_with(other_key)
+                    // Identical roots keep only their first occurrence
+                    && (key != other_key || other < *index)
+            })
+        })
+        .map(|(_, root)| root.clone())
+        .collect()
+}
+
+/// Normalized form of a root used to detect overlaps
+fn root_key(root: &str) -> PathBuf {
+    let path = std::path::absolute(Path::new(root)).unwrap_or_else(|_| PathBuf::from(root));
+
+    // NTFS paths are case-insensitive
+    #[cfg(windows)]
+    let path = PathBuf::from(path.to_string_lossy().to_lowercase());
+
+    path.components().collect()
+}
+
+/// Run `operation` on every root, reporting per-root and total statistics
+///
+/// A root that fails outright is logged and counted as a failure so the
+/// remaining roots are still processed.
+pub fn process_roots<F>(roots: &[String], config: &mut Config, mut operation: F) -> Result<TargetStats>
+where
+    F: FnMut(&str, &mut Config) -> Result<UnblockStats>,
+{
+    let mut result = TargetStats::default();
+
+    for root in roots {
+        if let Some(reason) = config.cancel.reason() {
+            result.total.cancelled = Some(reason);
+            break;
+        }
+        config.target_path = root.clone();
+
+        let stats = match operation(root, config) {
+            Ok(stats) => stats,
+            Err(e) => {
+                log_message(&format!("Error processing {}: {}", root, e.user_message()), config)?;
+                let record = FileRecord { path: root.into(), outcome: Err(FileFailure::from(&e)) };
+                emit(&Event::File(&FileReport::from(&record)), config)?;
+                let records = if config.collect_records { vec![record] } else { Vec::new() };
+                UnblockStats {
+                    files_processed: 1,
+                    files_failed: 1,
+                    permission_errors: usize::from(e.requires_elevation()),
+                    simulated: config.dry_run,
+                    records,
+                    ..Default::default()
+                }
+            }
+        };
+
+        if roots.len() > 1 {
+            log_message(&format!("{}: {}", root, stats.summary()), config)?;
+        }
+
+        result.total.merge(&stats);
+        result.roots.push(RootStats { root: root.clone(), stats });
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+    use crate::unblocker::process_target;
+    use std::fs::File;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_expand_response_files() {
+        let temp_dir = tempdir().unwrap();
+        let response = temp_dir.path().join("args.rsp");
+        fs::write(&response, "--verbose\r\n# comment\r\n\r\n\"C:\\Users\\me\\My Downloads\"\r\n").unwrap();
+
+        let args = vec![
+            "unblocker.exe".to_string(),
+            format!("@{}", response.display()),
+            "D:\\other".to_string(),
+        ];
+        let expanded = expand_response_files(args).unwrap();
+        assert_eq!(expanded, vec!["unblocker.exe", "--verbose", "C:\\Users\\me\\My Downloads", "D:\\other"]);
+
+        let missing = vec!["unblocker.exe".to_string(), "@/nonexistent/args.rsp".to_string()];
+        assert!(matches!(expand_response_files(missing), Err(UnblockerError::Config(_))));
+    }
+
+    #[test]
+    fn test_read_path_list() {
+        let temp_dir = tempdir().unwrap();
+        let lines = temp_dir.path().join("lines.txt");
+        let nul = temp_dir.path().join("nul.txt");
+        fs::write(&lines, "a.txt\r\nwith space.txt\n\n").unwrap();
+        fs::write(&nul, "first\nline\0second\0").unwrap();
+
+        assert_eq!(read_path_list(&lines.to_string_lossy()).unwrap(), vec!["a.txt", "with space.txt"]);
+        assert_eq!(read_path_list(&nul.to_string_lossy()).unwrap(), vec!["first\nline", "second"]);
+
+        let paths = vec!["C:\\Downloads\\a b.zip".to_string(), "first\nline".to_string()];
+        let written = write_path_list(&paths, temp_dir.path()).unwrap();
+        let again = write_path_list(&paths, temp_dir.path()).unwrap();
+        assert_ne!(written, again);
+        assert_eq!(read_path_list(&written.to_string_lossy()).unwrap(), paths);
+    }
+
+    #[test]
+    fn test_dedupe_roots() {
+        let roots = vec![
+            "/data/downloads/sub".to_string(),
+            "/data/downloads".to_string(),
+            "/data/other".to_string(),
+            "/data/downloads/".to_string(),
+            "/data/downloads/file.zip".to_string(),
+        ];
+        assert_eq!(dedupe_roots(roots), vec!["/data/downloads", "/data/other"]);
+    }
+
+    #[test]
+    fn test_process_roots_totals() {
+        let temp_dir = tempdir().unwrap();
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&first, &second] {
+            File::create(path).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.backend = backend;
+
+        let roots = vec![
+            first.to_string_lossy().to_string(),
+            second.to_string_lossy().to_string(),
+            temp_dir.path().join("missing.txt").to_string_lossy().to_string(),
+        ];
+        let result = process_roots(&roots, &mut config, process_target).unwrap();
+        assert_eq!(result.roots.len(), 3);
+        assert_eq!(result.roots[0].stats.files_unblocked, 1);
+        assert_eq!(result.roots[2].stats.files_failed, 1);
+        assert_eq!(result.total.files_processed, 3);
+        assert_eq!(result.total.files_unblocked, 2);
+        assert_eq!(result.total.files_failed, 1);
+    }
+}
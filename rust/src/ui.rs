@@ -1,10 +1,9 @@
 //! User interface utilities for message boxes and logging.
 
 use crate::config::Config;
-use crate::error::{Result, UnblockerError};
-use chrono::Utc;
-use std::fs::OpenOptions;
-use std::io::Write;
+use crate::error::Result;
+use crate::logging::{Verbosity, LOG_TARGET};
+use log::Level;
 
 #[cfg(windows)]
 use windows::{
@@ -34,63 +33,34 @@ pub fn show_message_box(text: &str, _caption: &str, _flags: u32) {
     eprintln!("{}", text);
 }
 
-/// Log a message with proper formatting and timestamps
+/// Log a message to the console and log file, as configured
 pub fn log_message(message: &str, config: &Config) -> Result<()> {
-    // Only format timestamp and log if we're actually going to use it
-    let should_log = config.verbose || config.log_path.is_some();
-    if !should_log {
-        return Ok(());
-    }
-    
-    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
-    let formatted_message = format!("[{}] {}", timestamp, message);
-    
-    if config.verbose {
-        println!("{}", formatted_message);
-    }
-
-    if let Some(log_path) = &config.log_path {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_path)
-            .map_err(|e| UnblockerError::Io(e))?;
-            
-        writeln!(file, "{}", formatted_message)
-            .map_err(|e| UnblockerError::Io(e))?;
-    }
-    
-    Ok(())
+    config.logger.log(Level::Info, LOG_TARGET, message, config)
 }
 
 /// Display an error message to the user via appropriate channel
 pub fn show_error(message: &str, config: &Config) {
-    if config.verbose {
-        eprintln!("ERROR: {}", message);
-    } else if config.log_path.is_none() {
+    // Without a console, log file or observer, a dialog is the only way to reach the user
+    if config.verbosity == Verbosity::Normal && config.log_path.is_none() && config.observer.is_none() {
         #[cfg(windows)]
         show_message_box(message, "SaltSpectre's File Unblocker", MB_OK | MB_ICONERROR);
         #[cfg(not(windows))]
         show_message_box(message, "SaltSpectre's File Unblocker", 0);
     }
     
-    // Always try to log errors if possible
-    let _ = log_message(&format!("ERROR: {}", message), config);
+    let _ = config.logger.log(Level::Error, LOG_TARGET, message, config);
 }
 
 /// Display a warning message to the user
 pub fn show_warning(message: &str, config: &Config) {
-    if config.verbose {
-        println!("WARNING: {}", message);
-    } else if config.log_path.is_none() {
+    if config.verbosity == Verbosity::Normal && config.log_path.is_none() && config.observer.is_none() {
         #[cfg(windows)]
         show_message_box(message, "SaltSpectre's File Unblocker", MB_OK | MB_ICONWARNING);
         #[cfg(not(windows))]
         show_message_box(message, "SaltSpectre's File Unblocker", 0);
     }
     
-    // Always try to log warnings if possible
-    let _ = log_message(&format!("WARNING: {}", message), config);
+    let _ = config.logger.log(Level::Warn, LOG_TARGET, message, config);
 }
 
 #[cfg(test)]
@@ -109,6 +79,7 @@ mod tests {
         ).unwrap();
         
         log_message("Test message", &config).unwrap();
+        config.logger.flush().unwrap();
         
         let contents = fs::read_to_string(temp_file.path()).unwrap();
         assert!(contents.contains("Test message"));
@@ -1,174 +1,623 @@
 //! Core file unblocking functionality.
 
+use crate::cancel::CancelReason;
+use crate::checksum::{verify, ChecksumVerdict, SidecarCache};
 use crate::config::Config;
 use crate::error::{Result, UnblockerError};
-use crate::path_utils::{get_ads_path, is_safe_path, validate_path};
+use crate::journal::JournalEntry;
+use crate::origin::{OriginPolicy, OriginVerdict};
+use crate::path_utils::{is_outside_protected, validate_path, ZONE_IDENTIFIER_STREAM};
+use crate::risk::{classify, RiskLevel};
+use crate::scan_hook::{run_scan, split_command, ScanVerdict};
 use crate::ui::{log_message, show_warning};
-use std::fs;
-use std::path::Path;
-use walkdir::WalkDir;
+use crate::walker::{walk_directory, walk_target};
+use crate::zone::{rewrite_zone_id, Zone, ZoneInfo};
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
 
 /// Statistics about the unblocking operation
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize)]
 pub struct UnblockStats {
     pub files_processed: usize,
     pub files_unblocked: usize,
     pub files_no_ads: usize,
     pub files_failed: usize,
     pub permission_errors: usize,
+    /// Files that still carry a Zone.Identifier stream after a read-only scan
+    pub files_detected: usize,
+    /// Files left untouched because of a safety check
+    pub files_skipped: usize,
+    /// Files that had a Zone.Identifier stream written by block mode
+    pub files_blocked: usize,
+    /// Files left blocked because they are marked as Restricted
+    pub files_restricted: usize,
+    /// Files left blocked because their zone is not selected for unblocking
+    pub files_zone_skipped: usize,
+    /// Files whose Zone.Identifier stream was rewritten to a lower zone
+    pub files_downgraded: usize,
+    /// Original streams recorded in the undo journal before being changed
+    pub files_journaled: usize,
+    /// Streams put back from an undo journal
+    pub files_restored: usize,
+    /// Set when the counts describe a dry run in which nothing was changed
+    pub simulated: bool,
+    /// Files left blocked because their type is high-risk and no override was given
+    pub files_high_risk: usize,
+    /// Files left out by the include and exclude filters
+    pub files_filtered: usize,
+    /// Files left blocked because their HostUrl or ReferrerUrl is denied or not trusted
+    pub files_untrusted_origin: usize,
+    /// Files left blocked because their SHA-256 differs from the listed checksum
+    pub files_checksum_mismatch: usize,
+    /// Files left blocked because no manifest or sidecar lists their checksum
+    pub files_checksum_unlisted: usize,
+    /// Files left blocked because the scanner command exited with a non-zero code
+    pub files_scan_rejected: usize,
+    /// Files left blocked because the scanner timed out or could not be started
+    pub files_scan_failed: usize,
+    /// Set when the run stopped before every file was processed
+    pub cancelled: Option<CancelReason>,
+    /// Per-file outcomes in traversal order, filled only when records are requested
+    #[serde(skip)]
+    pub records: Vec<FileRecord>,
 }
 
 impl UnblockStats {
     /// Create a summary message for the statistics
     pub fn summary(&self) -> String {
-        format!(
-            "Processed {} files: {} unblocked, {} had no ADS, {} failed ({} permission errors)",
+        let mut summary = format!(
+            "{}{}Processed {} files: {} unblocked, {} had no ADS, {} failed ({} permission errors)",
+            if self.simulated { "[DRY RUN] " } else { "" },
+            self.cancelled.map(|reason| format!("[STOPPED EARLY: {}] ", reason)).unwrap_or_default(),
             self.files_processed,
             self.files_unblocked,
             self.files_no_ads,
             self.files_failed,
             self.permission_errors
-        )
+        );
+        
+        let extras = [
+            (self.files_detected, "blocked (left unchanged)"),
+            (self.files_blocked, "marked as blocked"),
+            (self.files_restricted, "kept blocked (Restricted zone)"),
+            (self.files_zone_skipped, "kept blocked (zone not selected)"),
+            (self.files_high_risk, "kept blocked (high-risk file type)"),
+            (self.files_untrusted_origin, "kept blocked (origin not trusted)"),
+            (self.files_checksum_mismatch, "kept blocked (checksum mismatch)"),
+            (self.files_checksum_unlisted, "kept blocked (checksum not listed)"),
+            (self.files_scan_rejected, "kept blocked (rejected by scanner)"),
+            (self.files_scan_failed, "kept blocked (scan failed or timed out)"),
+            (self.files_downgraded, "downgraded"),
+            (self.files_journaled, "journaled"),
+            (self.files_restored, "restored"),
+            (self.files_skipped, "skipped"),
+            (self.files_filtered, "filtered out"),
+        ];
+        for (count, label) in extras {
+            if count > 0 {
+                summary.push_str(&format!(", {} {}", count, label));
+            }
+        }
+        
+        summary
+    }
+    
+    /// Count the outcome of a successfully processed file
+    pub fn record(&mut self, outcome: &FileOutcome) {
+        match outcome {
+            FileOutcome::Unblocked => self.files_unblocked += 1,
+            FileOutcome::NoAds => self.files_no_ads += 1,
+            FileOutcome::Detected(_) => self.files_detected += 1,
+            FileOutcome::Blocked => self.files_blocked += 1,
+            FileOutcome::Downgraded => self.files_downgraded += 1,
+            FileOutcome::Restored => self.files_restored += 1,
+            FileOutcome::Skipped(SkipReason::Zone(Zone::Restricted)) => self.files_restricted += 1,
+            FileOutcome::Skipped(SkipReason::Zone(_) | SkipReason::UnknownZone) => self.files_zone_skipped += 1,
+            FileOutcome::Skipped(SkipReason::HighRisk) => self.files_high_risk += 1,
+            FileOutcome::Skipped(SkipReason::Filtered) => self.files_filtered += 1,
+            FileOutcome::Skipped(SkipReason::Origin(_)) => self.files_untrusted_origin += 1,
+            FileOutcome::Skipped(SkipReason::Checksum(ChecksumVerdict::Mismatch)) => self.files_checksum_mismatch += 1,
+            FileOutcome::Skipped(SkipReason::Checksum(ChecksumVerdict::Unlisted)) => self.files_checksum_unlisted += 1,
+            FileOutcome::Skipped(SkipReason::Scan(ScanVerdict::Rejected(_))) => self.files_scan_rejected += 1,
+            FileOutcome::Skipped(SkipReason::Scan(_)) => self.files_scan_failed += 1,
+            FileOutcome::Skipped(_) => self.files_skipped += 1,
+        }
+    }
+    
+    /// Add the counts and records of another run, e.g. to total several targets
+    pub fn merge(&mut self, other: &UnblockStats) {
+        self.files_processed += other.files_processed;
+        self.files_unblocked += other.files_unblocked;
+        self.files_no_ads += other.files_no_ads;
+        self.files_failed += other.files_failed;
+        self.permission_errors += other.permission_errors;
+        self.files_detected += other.files_detected;
+        self.files_skipped += other.files_skipped;
+        self.files_blocked += other.files_blocked;
+        self.files_restricted += other.files_restricted;
+        self.files_zone_skipped += other.files_zone_skipped;
+        self.files_downgraded += other.files_downgraded;
+        self.files_journaled += other.files_journaled;
+        self.files_restored += other.files_restored;
+        self.files_high_risk += other.files_high_risk;
+        self.files_filtered += other.files_filtered;
+        self.files_untrusted_origin += other.files_untrusted_origin;
+        self.files_checksum_mismatch += other.files_checksum_mismatch;
+        self.files_checksum_unlisted += other.files_checksum_unlisted;
+        self.files_scan_rejected += other.files_scan_rejected;
+        self.files_scan_failed += other.files_scan_failed;
+        self.simulated |= other.simulated;
+        self.cancelled = self.cancelled.or(other.cancelled);
+        self.records.extend(other.records.iter().cloned());
     }
 }
 
-/// Unblock a single file by removing its Zone.Identifier ADS
-pub fn unblock_file(file_path: &str, config: &mut Config) -> Result<bool> {
+/// What happened to a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOutcome {
+    /// The Zone.Identifier stream was removed
+    Unblocked,
+    /// The file had no Zone.Identifier stream
+    NoAds,
+    /// The file has a Zone.Identifier stream that was deliberately left in place,
+    /// with its parsed contents if they could be read
+    Detected(Option<ZoneInfo>),
+    /// A Zone.Identifier stream was written to the file
+    Blocked,
+    /// The Zone.Identifier stream was rewritten to a lower zone
+    Downgraded,
+    /// A Zone.Identifier stream was put back from the undo journal
+    Restored,
+    /// The file was not touched
+    Skipped(SkipReason),
+}
+
+/// The outcome of a single file, kept for reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    pub path: PathBuf,
+    pub outcome: std::result::Result<FileOutcome, FileFailure>,
+}
+
+/// Why a file could not be processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFailure {
+    /// Machine-readable error kind, see [`UnblockerError::kind`]
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl From<&UnblockerError> for FileFailure {
+    fn from(e: &UnblockerError) -> Self {
+        Self {
+            kind: e.kind(),
+            message: e.user_message(),
+        }
+    }
+}
+
+/// Why a file was left untouched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file lives under a protected system path
+    UnsafePath,
+    /// The file's zone is not selected for unblocking
+    Zone(Zone),
+    /// A zone filter is active but the file's zone could not be determined
+    UnknownZone,
+    /// The file is already at or below the zone it would be downgraded to
+    AlreadyDowngraded,
+    /// The file type is high-risk and risky files are not allowed
+    HighRisk,
+    /// The file is left out by the include and exclude filters
+    Filtered,
+    /// The file's HostUrl or ReferrerUrl is denied, or not on the trusted list
+    Origin(OriginVerdict),
+    /// Checksum verification is required and the file's SHA-256 is wrong or not listed
+    Checksum(ChecksumVerdict),
+    /// The scanner command did not clear the file
+    Scan(ScanVerdict),
+}
+
+impl SkipReason {
+    /// Short description used in log output
+    pub fn describe(&self) -> String {
+        match self {
+            SkipReason::UnsafePath => "protected system path".to_string(),
+            SkipReason::Zone(zone) => format!("zone {} not selected for unblocking", zone),
+            SkipReason::UnknownZone => "zone could not be determined".to_string(),
+            SkipReason::AlreadyDowngraded => "already at or below the downgrade zone".to_string(),
+            SkipReason::HighRisk => "high-risk file type".to_string(),
+            SkipReason::Filtered => "excluded by filter".to_string(),
+            SkipReason::Origin(OriginVerdict::Denied) => "origin is denied".to_string(),
+            SkipReason::Origin(OriginVerdict::NotTrusted) => "origin is not trusted".to_string(),
+            SkipReason::Checksum(ChecksumVerdict::Mismatch) => "SHA-256 does not match the listed checksum".to_string(),
+            SkipReason::Checksum(ChecksumVerdict::Unlisted) => "SHA-256 not listed in any manifest".to_string(),
+            SkipReason::Scan(ScanVerdict::Rejected(Some(code))) => format!("scanner exited with code {}", code),
+            SkipReason::Scan(ScanVerdict::Rejected(None)) => "scanner was terminated".to_string(),
+            SkipReason::Scan(ScanVerdict::TimedOut) => "scanner timed out".to_string(),
+            SkipReason::Scan(ScanVerdict::Failed) => "scanner could not be started".to_string(),
+        }
+    }
+    
+    /// Stable machine-readable name, used in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SkipReason::UnsafePath => "unsafe_path",
+            SkipReason::Zone(_) => "zone",
+            SkipReason::UnknownZone => "unknown_zone",
+            SkipReason::AlreadyDowngraded => "already_downgraded",
+            SkipReason::HighRisk => "high_risk",
+            SkipReason::Filtered => "filtered",
+            SkipReason::Origin(OriginVerdict::Denied) => "denied_origin",
+            SkipReason::Origin(OriginVerdict::NotTrusted) => "untrusted_origin",
+            SkipReason::Checksum(ChecksumVerdict::Mismatch) => "checksum_mismatch",
+            SkipReason::Checksum(ChecksumVerdict::Unlisted) => "checksum_unlisted",
+            SkipReason::Scan(ScanVerdict::Rejected(_)) => "scan_rejected",
+            SkipReason::Scan(ScanVerdict::TimedOut) => "scan_timeout",
+            SkipReason::Scan(ScanVerdict::Failed) => "scan_failed",
+        }
+    }
+}
+
+/// Validate a file path and apply the system path guard
+///
+/// Returns `None` when the file must be skipped.
+pub(crate) fn prepare_file(file_path: &str, config: &Config) -> Result<Option<PathBuf>> {
     let file_path = validate_path(file_path)?;
     
-    if !is_safe_path(&file_path) {
+    if !is_outside_protected(&file_path, &config.protected_paths) {
         show_warning(
             &format!("Skipping potentially dangerous system path: {}", file_path.display()),
             config
         );
-        return Ok(false);
+        return Ok(None);
     }
     
-    let ads_path = get_ads_path(&file_path)?;
+    Ok(Some(file_path))
+}
+
+/// Read the raw Zone.Identifier stream of a file, if it has one
+pub(crate) fn read_zone_stream(file_path: &Path, config: &mut Config) -> Result<Option<Vec<u8>>> {
+    let backend = config.backend.clone();
     
-    match fs::remove_file(&ads_path) {
-        Ok(_) => {
-            log_message(&format!("Unblocked: {}", file_path.display()), config)?;
-            Ok(true)
+    match backend.read_stream(file_path, ZONE_IDENTIFIER_STREAM) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(stream_error(e, file_path, "Failed to read Zone.Identifier", config)?),
+    }
+}
+
+/// Log a failed stream operation and convert it into an error
+///
+/// Permission failures flag the run as needing elevation.
+pub(crate) fn stream_error(
+    e: io::Error,
+    file_path: &Path,
+    failure: &str,
+    config: &mut Config,
+) -> Result<UnblockerError> {
+    if e.kind() == io::ErrorKind::PermissionDenied {
+        config.set_requires_elevation();
+        log_message(
+            &format!("Access denied, requires elevation: {}", file_path.display()),
+            config
+        )?;
+        Ok(UnblockerError::PermissionDenied(file_path.display().to_string()))
+    } else {
+        log_message(
+            &format!("{}: {} — {}", failure, file_path.display(), e),
+            config
+        )?;
+        Ok(UnblockerError::Io(e))
+    }
+}
+
+/// Unblock a single file by removing its Zone.Identifier ADS
+pub fn unblock_file(file_path: &str, config: &mut Config) -> Result<bool> {
+    Ok(unblock_file_outcome(file_path, config)? == FileOutcome::Unblocked)
+}
+
+/// Unblock policies, the scanner command and checksum lists, read once per run rather than for every file
+#[derive(Debug)]
+pub(crate) struct FileChecks {
+    origins: OriginPolicy,
+    scan_command: Option<Vec<String>>,
+    sidecars: Option<SidecarCache>,
+}
+
+impl FileChecks {
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            origins: OriginPolicy::new(&config.trusted_origins, &config.denied_origins)?,
+            scan_command: config.scan_command.as_deref().map(split_command).transpose()?,
+            sidecars: config.verify_checksums.then(SidecarCache::new),
+        })
+    }
+
+    /// Read checksum lists again for the files checked from now on
+    pub(crate) fn forget_sidecars(&self) {
+        if let Some(sidecars) = &self.sidecars {
+            sidecars.clear();
         }
-        Err(e) => match e.kind() {
-            std::io::ErrorKind::NotFound => {
+    }
+}
+
+/// Unblock a single file and report exactly what happened to it
+pub fn unblock_file_outcome(file_path: &str, config: &mut Config) -> Result<FileOutcome> {
+    let checks = FileChecks::new(config)?;
+    let outcome = unblock_checked(file_path, &checks, config);
+    config.journal.sync()?;
+    outcome
+}
+
+/// Unblock a single file with policies that were already parsed
+pub(crate) fn unblock_checked(file_path: &str, checks: &FileChecks, config: &mut Config) -> Result<FileOutcome> {
+    let file_path = match prepare_file(file_path, config)? {
+        Some(file_path) => file_path,
+        None => return Ok(FileOutcome::Skipped(SkipReason::UnsafePath)),
+    };
+    
+    // Read the stream up front only when a policy, the journal or the chosen action needs its contents
+    let high_risk = !config.allow_risky && classify(&file_path, &config.risky_extensions) == RiskLevel::High;
+    let needs_contents = high_risk
+        || !checks.origins.is_empty()
+        || config.unblock_zones.is_some()
+        || config.downgrade_to.is_some()
+        || config.journal_path.is_some();
+    let stream = if needs_contents {
+        match read_zone_stream(&file_path, config)? {
+            Some(data) => Some(data),
+            None => {
                 log_message(&format!("No ADS found: {}", file_path.display()), config)?;
-                Ok(false)
+                return Ok(FileOutcome::NoAds);
             }
-            std::io::ErrorKind::PermissionDenied => {
-                config.set_requires_elevation();
-                log_message(
-                    &format!("Access denied, requires elevation: {}", file_path.display()),
-                    config
-                )?;
-                Err(UnblockerError::PermissionDenied(file_path.display().to_string()))
-            }
-            _ => {
-                log_message(
-                    &format!("Failed to unblock: {} — {}", file_path.display(), e),
-                    config
-                )?;
-                Err(UnblockerError::Io(e))
+        }
+    } else {
+        None
+    };
+    let zone_info = stream.as_deref().and_then(|data| ZoneInfo::parse(data).ok());
+    
+    if let Some(reason) = check_policy(high_risk, &checks.origins, zone_info.as_ref(), config) {
+        log_message(&format!("Kept blocked ({}): {}", reason.describe(), file_path.display()), config)?;
+        return Ok(FileOutcome::Skipped(reason));
+    }
+    
+    // Hashing reads the whole file, so it only runs once every cheaper check has passed
+    if config.hash_manifest.is_some() || checks.sidecars.is_some() {
+        let verdict = match verify(&file_path, config.hash_manifest.as_deref(), checks.sidecars.as_ref()) {
+            Ok(verdict) => verdict,
+            Err(e) => return Err(stream_error(e, &file_path, "Failed to verify checksum", config)?),
+        };
+        if let Some(verdict) = verdict {
+            let reason = SkipReason::Checksum(verdict);
+            log_message(&format!("Kept blocked ({}): {}", reason.describe(), file_path.display()), config)?;
+            return Ok(FileOutcome::Skipped(reason));
+        }
+    }
+    
+    // The scanner runs last, on files that would otherwise be unblocked
+    if let Some(command) = &checks.scan_command {
+        let outcome = run_scan(
+            command,
+            &file_path,
+            config.scan_timeout,
+            &config.scan_slots,
+            config.scan_concurrency,
+        );
+        if !outcome.output.is_empty() {
+            log_message(&format!("Scanner output for {}: {}", file_path.display(), outcome.output), config)?;
+        }
+        match outcome.verdict {
+            Some(verdict) => {
+                let reason = SkipReason::Scan(verdict);
+                log_message(&format!("Kept blocked ({}): {}", reason.describe(), file_path.display()), config)?;
+                return Ok(FileOutcome::Skipped(reason));
             }
+            None => log_message(&format!("Scanner passed: {}", file_path.display()), config)?,
         }
     }
+    
+    // The original stream is handed to the OS before it is changed, and the journal is synced every `SYNC_BATCH` entries
+    if let (Some(journal_path), Some(data), false) = (&config.journal_path, &stream, config.dry_run) {
+        config.journal.append(journal_path, &JournalEntry::new(&file_path, data))?;
+    }
+    
+    match config.downgrade_to {
+        Some(zone) => downgrade_zone_stream(&file_path, stream.as_deref(), zone_info, zone, config),
+        None => remove_zone_stream(&file_path, stream.is_some(), config),
+    }
 }
 
-/// Unblock all files in a directory recursively
-pub fn unblock_directory(dir_path: &str, config: &mut Config) -> Result<UnblockStats> {
-    let dir_path = validate_path(dir_path)?;
-    let mut stats = UnblockStats::default();
-    
-    log_message(&format!("Processing directory: {}", dir_path.display()), config)?;
-    
-    for entry in WalkDir::new(&dir_path) {
-        match entry {
-            Ok(entry) => {
-                if entry.file_type().is_file() {
-                    stats.files_processed += 1;
-                    
-                    let path_str = entry.path().to_string_lossy();
-                    match unblock_file(&path_str, config) {
-                        Ok(true) => stats.files_unblocked += 1,
-                        Ok(false) => stats.files_no_ads += 1,
-                        Err(UnblockerError::PermissionDenied(_)) => {
-                            stats.permission_errors += 1;
-                            stats.files_failed += 1;
-                        }
-                        Err(e) => {
-                            log_message(
-                                &format!("Error processing {}: {}", entry.path().display(), e),
-                                config
-                            )?;
-                            stats.files_failed += 1;
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                let error_path = e.path().map(|p| p.display().to_string())
-                    .unwrap_or_else(|| "unknown".to_string());
-                    
-                if e.io_error()
-                    .map(|io_err| io_err.kind() == std::io::ErrorKind::PermissionDenied)
-                    .unwrap_or(false)
-                {
-                    config.set_requires_elevation();
-                    log_message(
-                        &format!("Access denied to directory: {}", error_path),
-                        config
-                    )?;
-                    stats.permission_errors += 1;
-                } else {
-                    log_message(
-                        &format!("Failed to enumerate directory: {} — {}", error_path, e),
-                        config
-                    )?;
-                }
-                stats.files_failed += 1;
-            }
+/// Apply the configured unblock policy to a file that is about to be unblocked
+///
+/// Returns the reason to keep the file blocked, if any.
+fn check_policy(
+    high_risk: bool,
+    origins: &OriginPolicy,
+    zone_info: Option<&ZoneInfo>,
+    config: &Config,
+) -> Option<SkipReason> {
+    if high_risk {
+        return Some(SkipReason::HighRisk);
+    }
+    
+    if let Some(zones) = &config.unblock_zones {
+        match zone_info.and_then(|info| info.zone_id) {
+            Some(zone) if zones.contains(&zone) => {}
+            Some(zone) => return Some(SkipReason::Zone(zone)),
+            None => return Some(SkipReason::UnknownZone),
         }
     }
     
-    log_message(&stats.summary(), config)?;
-    Ok(stats)
+    origins.check(zone_info).map(SkipReason::Origin)
 }
 
-/// Process a target path (either file or directory)
-pub fn process_target(target_path: &str, config: &mut Config) -> Result<UnblockStats> {
-    let path = Path::new(target_path);
-    
-    if path.is_file() {
-        let mut stats = UnblockStats::default();
-        stats.files_processed = 1;
-        
-        match unblock_file(target_path, config) {
-            Ok(true) => stats.files_unblocked = 1,
-            Ok(false) => stats.files_no_ads = 1,
-            Err(UnblockerError::PermissionDenied(_)) => {
-                stats.permission_errors = 1;
-                stats.files_failed = 1;
-            }
-            Err(e) => {
-                stats.files_failed = 1;
-                return Err(e);
-            }
+/// Prefix for log lines describing changes that a dry run only simulates
+fn simulated(config: &Config) -> &'static str {
+    if config.dry_run {
+        "[DRY RUN] "
+    } else {
+        ""
+    }
+}
+
+/// Delete the Zone.Identifier stream of a file
+///
+/// `known_present` is set when the stream has already been read, so a dry run
+/// does not need to look for it again.
+fn remove_zone_stream(file_path: &Path, known_present: bool, config: &mut Config) -> Result<FileOutcome> {
+    let backend = config.backend.clone();
+    
+    let result = if !config.dry_run {
+        backend.remove_stream(file_path, ZONE_IDENTIFIER_STREAM)
+    } else if known_present {
+        Ok(())
+    } else {
+        backend.read_stream(file_path, ZONE_IDENTIFIER_STREAM).map(|_| ())
+    };
+    
+    match result {
+        Ok(_) => {
+            log_message(&format!("{}Unblocked: {}", simulated(config), file_path.display()), config)?;
+            Ok(FileOutcome::Unblocked)
         }
-        
-        Ok(stats)
-    } else if path.is_dir() {
-        unblock_directory(target_path, config)
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            log_message(&format!("No ADS found: {}", file_path.display()), config)?;
+            Ok(FileOutcome::NoAds)
+        }
+        Err(e) => Err(stream_error(e, file_path, "Failed to unblock", config)?),
+    }
+}
+
+/// Rewrite the Zone.Identifier stream of a file to a lower zone
+///
+/// Every other key, including HostUrl and ReferrerUrl, is kept. A stream that
+/// could not be parsed keeps its lines and only gets the new ZoneId.
+fn downgrade_zone_stream(
+    file_path: &Path,
+    stream: Option<&[u8]>,
+    zone_info: Option<ZoneInfo>,
+    target: Zone,
+    config: &mut Config,
+) -> Result<FileOutcome> {
+    let current = zone_info.as_ref().and_then(|zone_info| zone_info.zone_id);
+    
+    if let Some(current) = current {
+        if !matches!(current, Zone::Other(_)) && current.id() <= target.id() {
+            log_message(
+                &format!("Already at or below zone {}: {}", target, file_path.display()),
+                config
+            )?;
+            return Ok(FileOutcome::Skipped(SkipReason::AlreadyDowngraded));
+        }
+    }
+    
+    let data = match (zone_info, stream) {
+        (Some(mut zone_info), _) => {
+            zone_info.zone_id = Some(target);
+            zone_info.to_bytes()
+        }
+        (None, Some(stream)) => rewrite_zone_id(stream, target),
+        (None, None) => ZoneInfo::new(target).to_bytes(),
+    };
+    let backend = config.backend.clone();
+    
+    let result = if config.dry_run {
+        Ok(())
     } else {
-        Err(UnblockerError::PathNotFound(target_path.to_string()))
+        backend.write_stream(file_path, ZONE_IDENTIFIER_STREAM, &data)
+    };
+    
+    match result {
+        Ok(_) => {
+            let from = current.map(|zone| zone.to_string()).unwrap_or_else(|| "unknown".to_string());
+            log_message(
+                &format!("{}Downgraded: {} (ZoneId {} -> {})", simulated(config), file_path.display(), from, target),
+                config
+            )?;
+            Ok(FileOutcome::Downgraded)
+        }
+        Err(e) => Err(stream_error(e, file_path, "Failed to downgrade", config)?),
     }
 }
 
+/// Block a single file by writing a Zone.Identifier ADS
+///
+/// Any existing Zone.Identifier stream is replaced.
+pub fn block_file(file_path: &str, zone_info: &ZoneInfo, config: &mut Config) -> Result<bool> {
+    Ok(block_file_outcome(file_path, zone_info, config)? == FileOutcome::Blocked)
+}
+
+fn block_file_outcome(file_path: &str, zone_info: &ZoneInfo, config: &mut Config) -> Result<FileOutcome> {
+    let file_path = match prepare_file(file_path, config)? {
+        Some(file_path) => file_path,
+        None => return Ok(FileOutcome::Skipped(SkipReason::UnsafePath)),
+    };
+    
+    let backend = config.backend.clone();
+    
+    let result = if config.dry_run {
+        Ok(())
+    } else {
+        backend.write_stream(&file_path, ZONE_IDENTIFIER_STREAM, &zone_info.to_bytes())
+    };
+    
+    match result {
+        Ok(_) => {
+            let zone = zone_info.zone_id
+                .map(|zone| format!("as zone {}", zone))
+                .unwrap_or_else(|| "without a zone".to_string());
+            log_message(&format!("{}Blocked: {} {}", simulated(config), file_path.display(), zone), config)?;
+            Ok(FileOutcome::Blocked)
+        }
+        Err(e) => Err(stream_error(e, &file_path, "Failed to block", config)?),
+    }
+}
+
+/// Unblock all files in a directory recursively
+pub fn unblock_directory(dir_path: &str, config: &mut Config) -> Result<UnblockStats> {
+    let collect_records = config.collect_records;
+    let checks = FileChecks::new(config)?;
+    let stats = walk_directory(dir_path, config, collect_records, |path, config| unblock_checked(path, &checks, config));
+    config.journal.sync()?;
+    stats
+}
+
+/// Apply Mark-of-the-Web to a target path (either file or directory)
+pub fn block_target(target_path: &str, zone_info: &ZoneInfo, config: &mut Config) -> Result<UnblockStats> {
+    let collect_records = config.collect_records;
+    walk_target(target_path, config, collect_records, |path, config| block_file_outcome(path, zone_info, config))
+}
+
+/// Process a target path (either file or directory)
+pub fn process_target(target_path: &str, config: &mut Config) -> Result<UnblockStats> {
+    let collect_records = config.collect_records;
+    let checks = FileChecks::new(config)?;
+    let stats = walk_target(target_path, config, collect_records, |path, config| unblock_checked(path, &checks, config));
+    // Journal entries still waiting for a sync reach the disk before the run reports its result
+    config.journal.sync()?;
+    let stats = stats?;
+    
+    // Outside verbose mode a single file kept blocked for its type would otherwise go unexplained
+    if stats.files_high_risk > 0 && Path::new(target_path).is_file() {
+        show_warning(
+            &format!("{} was kept blocked because it is a high-risk file type; use --allow-risky to unblock it anyway", target_path),
+            config
+        );
+    }
+    
+    Ok(stats)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use std::fs::{self, File};
     use std::io::Write;
+    use std::sync::Arc;
     use tempfile::tempdir;
     
     #[cfg(windows)]
@@ -233,6 +682,404 @@ mod tests {
         assert_eq!(stats.files_processed, 2);
     }
     
+    #[test]
+    fn test_process_target_memory_backend() {
+        let temp_dir = tempdir().unwrap();
+        let blocked = temp_dir.path().join("blocked.txt");
+        let clean = temp_dir.path().join("clean.txt");
+        File::create(&blocked).unwrap();
+        File::create(&clean).unwrap();
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&blocked, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_processed, 2);
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_no_ads, 1);
+        assert!(!backend.has_stream(&blocked, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[test]
+    fn test_unblock_file_permission_denied() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("locked.txt");
+        File::create(&file_path).unwrap();
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&file_path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        backend.deny_access(&file_path);
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        
+        let result = unblock_file(&file_path.to_string_lossy(), &mut config);
+        assert!(matches!(result, Err(UnblockerError::PermissionDenied(_))));
+        assert!(config.requires_elevation);
+    }
+    
+    #[test]
+    fn test_block_then_unblock_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let nested = temp_dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        let file1 = temp_dir.path().join("file1.txt");
+        let file2 = nested.join("file2.txt");
+        File::create(&file1).unwrap();
+        File::create(&file2).unwrap();
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        
+        let mut zone_info = ZoneInfo::new(Zone::Internet);
+        zone_info.host_url = Some("https://example.com/file.zip".to_string());
+        
+        let stats = block_target(&temp_dir.path().to_string_lossy(), &zone_info, &mut config).unwrap();
+        assert_eq!(stats.files_processed, 2);
+        assert_eq!(stats.files_blocked, 2);
+        
+        let written = backend.read_stream(&file2, ZONE_IDENTIFIER_STREAM).unwrap();
+        assert_eq!(ZoneInfo::parse(&written).unwrap(), zone_info);
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 2);
+        assert!(!backend.has_stream(&file1, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[test]
+    fn test_zone_filter_keeps_restricted_blocked() {
+        let temp_dir = tempdir().unwrap();
+        let backend = Arc::new(MemoryStreamBackend::new());
+        let mut files = Vec::new();
+        for (name, stream) in [
+            ("internet.txt", "[ZoneTransfer]\r\nZoneId=3\r\n"),
+            ("restricted.txt", "[ZoneTransfer]\r\nZoneId=4\r\n"),
+            ("intranet.txt", "[ZoneTransfer]\r\nZoneId=1\r\n"),
+            ("garbled.txt", "not a zone file"),
+        ] {
+            let path = temp_dir.path().join(name);
+            File::create(&path).unwrap();
+            backend.write_stream(&path, ZONE_IDENTIFIER_STREAM, stream.as_bytes()).unwrap();
+            files.push(path);
+        }
+        File::create(temp_dir.path().join("clean.txt")).unwrap();
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.unblock_zones = Some(vec![Zone::Internet]);
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_processed, 5);
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_restricted, 1);
+        assert_eq!(stats.files_zone_skipped, 2);
+        assert_eq!(stats.files_no_ads, 1);
+        
+        assert!(!backend.has_stream(&files[0], ZONE_IDENTIFIER_STREAM));
+        for path in &files[1..] {
+            assert!(backend.has_stream(path, ZONE_IDENTIFIER_STREAM));
+        }
+        assert!(stats.summary().contains("1 kept blocked (Restricted zone)"));
+    }
+    
+    #[test]
+    fn test_downgrade_keeps_origin() {
+        let temp_dir = tempdir().unwrap();
+        let internet = temp_dir.path().join("internet.zip");
+        let intranet = temp_dir.path().join("intranet.zip");
+        let corrupt = temp_dir.path().join("corrupt.zip");
+        for path in [&internet, &intranet, &corrupt] {
+            File::create(path).unwrap();
+        }
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(
+            &internet,
+            ZONE_IDENTIFIER_STREAM,
+            b"[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://example.com/\r\nHostUrl=https://example.com/internet.zip\r\n",
+        ).unwrap();
+        backend.write_stream(&intranet, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=1\r\n").unwrap();
+        backend.write_stream(
+            &corrupt,
+            ZONE_IDENTIFIER_STREAM,
+            b"[ZoneTransfer]\r\nZoneId=three\r\nHostUrl=https://example.com/corrupt.zip\r\n",
+        ).unwrap();
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.downgrade_to = Some(Zone::Intranet);
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_downgraded, 2);
+        assert_eq!(stats.files_skipped, 1);
+        assert_eq!(stats.files_unblocked, 0);
+        
+        let info = ZoneInfo::parse(&backend.read_stream(&internet, ZONE_IDENTIFIER_STREAM).unwrap()).unwrap();
+        assert_eq!(info.zone_id, Some(Zone::Intranet));
+        assert_eq!(info.referrer_url.as_deref(), Some("https://example.com/"));
+        assert_eq!(info.host_url.as_deref(), Some("https://example.com/internet.zip"));
+        assert!(backend.has_stream(&intranet, ZONE_IDENTIFIER_STREAM));
+        
+        let info = ZoneInfo::parse(&backend.read_stream(&corrupt, ZONE_IDENTIFIER_STREAM).unwrap()).unwrap();
+        assert_eq!(info.zone_id, Some(Zone::Intranet));
+        assert_eq!(info.host_url.as_deref(), Some("https://example.com/corrupt.zip"));
+    }
+    
+    #[test]
+    fn test_dry_run_changes_nothing() {
+        let temp_dir = tempdir().unwrap();
+        let blocked = temp_dir.path().join("blocked.txt");
+        let restricted = temp_dir.path().join("restricted.txt");
+        let clean = temp_dir.path().join("clean.txt");
+        for path in [&blocked, &restricted, &clean] {
+            File::create(path).unwrap();
+        }
+        let journal = temp_dir.path().join("journal.jsonl");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&blocked, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        backend.write_stream(&restricted, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=4\r\n").unwrap();
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.dry_run = true;
+        config.journal_path = Some(journal.to_string_lossy().to_string());
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 2);
+        assert_eq!(stats.files_no_ads, 1);
+        assert_eq!(stats.files_journaled, 0);
+        assert!(stats.summary().starts_with("[DRY RUN] Processed 3 files"));
+        assert!(backend.has_stream(&blocked, ZONE_IDENTIFIER_STREAM));
+        assert!(!journal.exists());
+        
+        // Policy checks still apply during a dry run
+        config.unblock_zones = Some(vec![Zone::Internet]);
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_restricted, 1);
+        assert!(backend.has_stream(&restricted, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[test]
+    fn test_include_exclude_filters() {
+        let temp_dir = tempdir().unwrap();
+        let cache = temp_dir.path().join("Cache");
+        std::fs::create_dir(&cache).unwrap();
+        let setup = temp_dir.path().join("Setup.ZIP");
+        let notes = temp_dir.path().join("notes.txt");
+        let cached = cache.join("old.zip");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&setup, &notes, &cached] {
+            File::create(path).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.include = vec!["*.zip".to_string()];
+        config.exclude = vec!["cache".to_string()];
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_processed, 2);
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_filtered, 1);
+        assert!(stats.summary().ends_with(", 1 filtered out"));
+        assert!(!backend.has_stream(&setup, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&notes, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&cached, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[test]
+    fn test_high_risk_files_kept_blocked() {
+        let temp_dir = tempdir().unwrap();
+        let installer = temp_dir.path().join("setup.exe");
+        let macros = temp_dir.path().join("budget.XLSM");
+        let archive = temp_dir.path().join("photos.zip");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&installer, &macros, &archive] {
+            File::create(path).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_high_risk, 2);
+        assert!(backend.has_stream(&installer, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&macros, ZONE_IDENTIFIER_STREAM));
+        assert!(!unblock_file(&installer.to_string_lossy(), &mut config).unwrap());
+        
+        let log = temp_dir.path().join("unblocker.log");
+        config.log_path = Some(log.to_string_lossy().to_string());
+        let stats = process_target(&installer.to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_high_risk, 1);
+        config.logger.flush().unwrap();
+        assert!(fs::read_to_string(&log).unwrap().contains("kept blocked because it is a high-risk file type"));
+        config.log_path = None;
+        
+        config.allow_risky = true;
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 2);
+        assert_eq!(stats.files_high_risk, 0);
+        assert!(!backend.has_stream(&installer, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[test]
+    fn test_origin_policy_keeps_files_blocked() {
+        let temp_dir = tempdir().unwrap();
+        let internal = temp_dir.path().join("build.zip");
+        let vendor = temp_dir.path().join("driver.zip");
+        let unknown = temp_dir.path().join("random.zip");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        let streams: [(&PathBuf, &[u8]); 3] = [
+            (&internal, b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://artifacts.corp.example/build.zip\r\n"),
+            (&vendor, b"[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://downloads.vendor.example/\r\n"),
+            (&unknown, b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://mirror.example/random.zip\r\n"),
+        ];
+        for (path, stream) in streams {
+            File::create(path).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, stream).unwrap();
+        }
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.trusted_origins = vec!["corp.example".to_string(), "https://downloads.vendor.example/".to_string()];
+        config.denied_origins = vec!["https://downloads.vendor.example/".to_string()];
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_untrusted_origin, 2);
+        assert!(stats.summary().contains("2 kept blocked (origin not trusted)"));
+        assert!(!backend.has_stream(&internal, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&vendor, ZONE_IDENTIFIER_STREAM));
+        assert_eq!(
+            unblock_file_outcome(&vendor.to_string_lossy(), &mut config).unwrap(),
+            FileOutcome::Skipped(SkipReason::Origin(OriginVerdict::Denied))
+        );
+        assert_eq!(
+            unblock_file_outcome(&unknown.to_string_lossy(), &mut config).unwrap(),
+            FileOutcome::Skipped(SkipReason::Origin(OriginVerdict::NotTrusted))
+        );
+    }
+    
+    #[test]
+    fn test_checksum_verification() {
+        let temp_dir = tempdir().unwrap();
+        let good = temp_dir.path().join("tool.zip");
+        let bad = temp_dir.path().join("driver.zip");
+        let extra = temp_dir.path().join("extra.zip");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&good, &bad, &extra] {
+            std::fs::write(path, path.file_name().unwrap().as_encoded_bytes()).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+        let sums = format!(
+            "{}  tool.zip\n{}  driver.zip\n",
+            crate::checksum::sha256_file(&good).unwrap(),
+            crate::checksum::sha256_file(&extra).unwrap()
+        );
+        std::fs::write(temp_dir.path().join(crate::checksum::SHA256SUMS_NAME), sums).unwrap();
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.include = vec!["*.zip".to_string()];
+        config.verify_checksums = true;
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_checksum_mismatch, 1);
+        assert_eq!(stats.files_checksum_unlisted, 1);
+        assert!(!backend.has_stream(&good, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&bad, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&extra, ZONE_IDENTIFIER_STREAM));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_scanner_hook() {
+        let temp_dir = tempdir().unwrap();
+        let clean = temp_dir.path().join("clean.zip");
+        let infected = temp_dir.path().join("eicar.zip");
+        
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&clean, &infected] {
+            File::create(path).unwrap();
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+        
+        let mut config = Config::new(
+            false,
+            None,
+            temp_dir.path().to_string_lossy().to_string(),
+        ).unwrap();
+        config.backend = backend.clone();
+        config.workers = 2;
+        config.scan_command = Some(r#"sh -c "case $0 in *eicar*) echo FOUND; exit 1;; esac" {file}"#.to_string());
+        
+        let stats = process_target(&temp_dir.path().to_string_lossy(), &mut config).unwrap();
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_scan_rejected, 1);
+        assert!(!backend.has_stream(&clean, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&infected, ZONE_IDENTIFIER_STREAM));
+        
+        config.scan_command = Some("/nonexistent/scanner".to_string());
+        assert_eq!(
+            unblock_file_outcome(&infected.to_string_lossy(), &mut config).unwrap(),
+            FileOutcome::Skipped(SkipReason::Scan(ScanVerdict::Failed))
+        );
+    }
+    
     #[test]
     fn test_unblock_stats_summary() {
         let stats = UnblockStats {
@@ -241,6 +1088,7 @@ mod tests {
             files_no_ads: 3,
             files_failed: 2,
             permission_errors: 1,
+            ..Default::default()
         };
         
         let summary = stats.summary();
@@ -250,4 +1098,5 @@ mod tests {
         assert!(summary.contains("2 failed"));
         assert!(summary.contains("1 permission errors"));
     }
+
 }
\ No newline at end of file
@@ -0,0 +1,465 @@
+//! Sequential and parallel traversal shared by every file operation.
+
+use crate::cancel::CancelReason;
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use crate::events::{emit, Event};
+use crate::filter::PathFilter;
+use crate::observer::{notify_finished, notify_started};
+use crate::path_utils::validate_path;
+use crate::report::FileReport;
+use crate::ui::log_message;
+use crate::unblocker::{FileFailure, FileOutcome, FileRecord, SkipReason, UnblockStats};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use walkdir::WalkDir;
+
+/// Number of queued paths per worker before enumeration waits for the workers
+const QUEUE_DEPTH_PER_WORKER: usize = 64;
+
+/// Collects per-file results into statistics, in the order files were enumerated
+pub(crate) struct Aggregator {
+    stats: UnblockStats,
+    /// Set when files were left unprocessed because the run was cancelled
+    pub(crate) cancelled: Option<CancelReason>,
+    collect_records: bool,
+    records: Vec<(usize, FileRecord)>,
+}
+
+impl Aggregator {
+    pub(crate) fn new(config: &Config, collect_records: bool) -> Self {
+        Self {
+            stats: UnblockStats {
+                simulated: config.dry_run,
+                ..Default::default()
+            },
+            cancelled: None,
+            collect_records,
+            records: Vec::new(),
+        }
+    }
+
+    /// Count the result of processing one file
+    pub(crate) fn file_result(
+        &mut self,
+        index: usize,
+        path: &Path,
+        result: Result<FileOutcome>,
+        config: &mut Config,
+    ) -> Result<()> {
+        self.stats.files_processed += 1;
+
+        match &result {
+            Ok(outcome) => tally(&mut self.stats, outcome, config),
+            Err(UnblockerError::PermissionDenied(_)) => {
+                config.set_requires_elevation();
+                emit(&Event::ElevationNeeded { path: path.display().to_string() }, config)?;
+                self.stats.permission_errors += 1;
+                self.stats.files_failed += 1;
+            }
+            Err(e) => {
+                log_message(
+                    &format!("Error processing {}: {}", path.display(), e),
+                    config
+                )?;
+                self.stats.files_failed += 1;
+            }
+        }
+
+        let record = FileRecord { path: path.to_path_buf(), outcome: result.map_err(|e| FileFailure::from(&e)) };
+        if let Some(observer) = &config.observer {
+            observer.file_result(&record);
+        }
+        self.keep(index, record, config)
+    }
+
+    /// Stream the record of a file and keep it for the report if requested
+    fn keep(&mut self, index: usize, record: FileRecord, config: &Config) -> Result<()> {
+        emit(&Event::File(&FileReport::from(&record)), config)?;
+
+        if self.collect_records {
+            self.records.push((index, record));
+        }
+        Ok(())
+    }
+
+    /// Count a directory that could not be enumerated
+    fn walk_error(&mut self, index: usize, e: walkdir::Error, config: &mut Config) -> Result<()> {
+        let error_path = e.path().map(|p| p.display().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let denied = e.io_error()
+            .map(|io_err| io_err.kind() == std::io::ErrorKind::PermissionDenied)
+            .unwrap_or(false);
+
+        if denied {
+            config.set_requires_elevation();
+            emit(&Event::ElevationNeeded { path: error_path.clone() }, config)?;
+            log_message(
+                &format!("Access denied to directory: {}", error_path),
+                config
+            )?;
+            self.stats.permission_errors += 1;
+        } else {
+            log_message(
+                &format!("Failed to enumerate directory: {} — {}", error_path, e),
+                config
+            )?;
+        }
+        self.stats.files_failed += 1;
+
+        let failure = if denied {
+            UnblockerError::PermissionDenied(error_path.clone())
+        } else {
+            UnblockerError::WalkDir(e)
+        };
+        let record = FileRecord { path: PathBuf::from(error_path), outcome: Err(FileFailure::from(&failure)) };
+        if let (Some(observer), Err(failure)) = (&config.observer, &record.outcome) {
+            observer.directory_error(&record.path, failure);
+        }
+        self.keep(index, record, config)
+    }
+
+    pub(crate) fn finish(mut self) -> UnblockStats {
+        self.stats.cancelled = self.cancelled;
+        self.records.sort_by_key(|(index, _)| *index);
+        self.stats.records = self.records.into_iter().map(|(_, record)| record).collect();
+        self.stats
+    }
+}
+
+/// Count the outcome of an operation, including whether its original stream was journaled
+fn tally(stats: &mut UnblockStats, outcome: &FileOutcome, config: &Config) {
+    stats.record(outcome);
+
+    if config.journal_path.is_some()
+        && !config.dry_run
+        && matches!(outcome, FileOutcome::Unblocked | FileOutcome::Downgraded)
+    {
+        stats.files_journaled += 1;
+    }
+}
+
+/// A directory or regular file found during enumeration
+enum Entry {
+    Directory(PathBuf),
+    /// A file and whether it passes the include and exclude filters
+    File(PathBuf, bool),
+}
+
+/// Enumerate the directories and files below `root`, pruning excluded directories
+fn entries<'a>(
+    root: &'a Path,
+    filter: &'a PathFilter,
+) -> impl Iterator<Item = walkdir::Result<Entry>> + 'a {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(move |entry| {
+            !(entry.file_type().is_dir() && filter.prunes_dir(relative_to(root, entry.path())))
+        })
+        .filter_map(move |entry| match entry {
+            Ok(entry) if entry.file_type().is_dir() => Some(Ok(Entry::Directory(entry.into_path()))),
+            Ok(entry) if entry.file_type().is_file() => {
+                let allowed = filter.allows_file(relative_to(root, entry.path()));
+                Some(Ok(Entry::File(entry.into_path(), allowed)))
+            }
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+}
+
+/// Count the files below `root` that a walk with `filter` would report
+pub(crate) fn count_files(root: &Path, filter: &PathFilter) -> u64 {
+    entries(root, filter).filter(|entry| matches!(entry, Ok(Entry::File(..)))).count() as u64
+}
+
+pub(crate) fn relative_to<'p>(root: &Path, path: &'p Path) -> &'p Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Events sent from the enumeration and worker threads to the aggregating thread
+enum WalkEvent {
+    Directory(PathBuf),
+    File(usize, PathBuf, Result<FileOutcome>),
+    WalkError(usize, walkdir::Error),
+}
+
+/// Walk a directory recursively and apply `action` to every regular file
+///
+/// With more than one worker configured, enumeration and processing run on
+/// separate threads. Statistics and records are still aggregated on the calling
+/// thread, and records are returned in enumeration order.
+pub(crate) fn walk_directory<F>(
+    dir_path: &str,
+    config: &mut Config,
+    collect_records: bool,
+    action: F,
+) -> Result<UnblockStats>
+where
+    F: Fn(&str, &mut Config) -> Result<FileOutcome> + Sync,
+{
+    let dir_path = validate_path(dir_path)?;
+    let filter = PathFilter::new(&config.include, &config.exclude)?;
+    let mut aggregator = Aggregator::new(config, collect_records);
+
+    log_message(&format!("Processing directory: {}", dir_path.display()), config)?;
+    notify_started(&dir_path, Some(&filter), config);
+
+    if config.effective_workers() > 1 {
+        walk_parallel(&dir_path, &filter, config, &mut aggregator, &action)?;
+    } else {
+        for (index, entry) in entries(&dir_path, &filter).enumerate() {
+            if let Some(reason) = config.cancel.reason() {
+                aggregator.cancelled = Some(reason);
+                break;
+            }
+            match entry {
+                Ok(Entry::Directory(path)) => emit(&Event::Directory { path: path.display().to_string() }, config)?,
+                Ok(Entry::File(path, true)) => {
+                    let result = action(&path.to_string_lossy(), config);
+                    aggregator.file_result(index, &path, result, config)?;
+                }
+                Ok(Entry::File(path, false)) => {
+                    let filtered = Ok(FileOutcome::Skipped(SkipReason::Filtered));
+                    aggregator.file_result(index, &path, filtered, config)?;
+                }
+                Err(e) => aggregator.walk_error(index, e, config)?,
+            }
+        }
+    }
+
+    let stats = aggregator.finish();
+    log_message(&stats.summary(), config)?;
+    notify_finished(&dir_path, &stats, config);
+    Ok(stats)
+}
+
+fn walk_parallel<F>(
+    dir_path: &Path,
+    filter: &PathFilter,
+    config: &mut Config,
+    aggregator: &mut Aggregator,
+    action: &F,
+) -> Result<()>
+where
+    F: Fn(&str, &mut Config) -> Result<FileOutcome> + Sync,
+{
+    let workers = config.effective_workers();
+    let stop = AtomicBool::new(false);
+    let cut_short = AtomicBool::new(false);
+    let (work_tx, work_rx) = mpsc::sync_channel::<(usize, PathBuf)>(workers * QUEUE_DEPTH_PER_WORKER);
+    let work_rx = Mutex::new(work_rx);
+    let (event_tx, event_rx) = mpsc::channel::<WalkEvent>();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let event_tx = event_tx.clone();
+            let mut worker_config = config.clone();
+            let (work_rx, stop, cut_short) = (&work_rx, &stop, &cut_short);
+
+            scope.spawn(move || loop {
+                let next = work_rx.lock().unwrap().recv();
+                let (index, path) = match next {
+                    Ok(item) => item,
+                    Err(_) => break,
+                };
+                // After a stop, queued files are drained unprocessed so enumeration is never left blocked
+                if stop.load(Ordering::Relaxed) {
+                    continue;
+                }
+                if worker_config.cancel.is_cancelled() {
+                    cut_short.store(true, Ordering::Relaxed);
+                    continue;
+                }
+
+                let result = action(&path.to_string_lossy(), &mut worker_config);
+                if event_tx.send(WalkEvent::File(index, path, result)).is_err() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let enum_tx = event_tx.clone();
+        let (stop_ref, cut_short_ref, cancel) = (&stop, &cut_short, config.cancel.clone());
+        scope.spawn(move || {
+            for (index, entry) in entries(dir_path, filter).enumerate() {
+                if stop_ref.load(Ordering::Relaxed) {
+                    break;
+                }
+                if cancel.is_cancelled() {
+                    cut_short_ref.store(true, Ordering::Relaxed);
+                    break;
+                }
+                let sent = match entry {
+                    Ok(Entry::Directory(path)) => enum_tx.send(WalkEvent::Directory(path)).is_ok(),
+                    Ok(Entry::File(path, true)) => work_tx.send((index, path)).is_ok(),
+                    Ok(Entry::File(path, false)) => {
+                        let filtered = Ok(FileOutcome::Skipped(SkipReason::Filtered));
+                        enum_tx.send(WalkEvent::File(index, path, filtered)).is_ok()
+                    }
+                    Err(e) => enum_tx.send(WalkEvent::WalkError(index, e)).is_ok(),
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+        drop(event_tx);
+
+        // Aggregate on this thread so only the caller's config is ever mutated
+        for event in event_rx {
+            let result = match event {
+                WalkEvent::Directory(path) => emit(&Event::Directory { path: path.display().to_string() }, config),
+                WalkEvent::File(index, path, result) => aggregator.file_result(index, &path, result, config),
+                WalkEvent::WalkError(index, e) => aggregator.walk_error(index, e, config),
+            };
+
+            if let Err(e) = result {
+                stop.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+
+        if cut_short.load(Ordering::Relaxed) {
+            aggregator.cancelled = config.cancel.reason();
+        }
+        Ok(())
+    })
+}
+
+/// Apply `action` to a single file or to every file below a directory
+pub(crate) fn walk_target<F>(
+    target_path: &str,
+    config: &mut Config,
+    collect_records: bool,
+    action: F,
+) -> Result<UnblockStats>
+where
+    F: Fn(&str, &mut Config) -> Result<FileOutcome> + Sync,
+{
+    let path = Path::new(target_path);
+
+    if path.is_file() {
+        let mut aggregator = Aggregator::new(config, collect_records);
+        notify_started(path, None, config);
+
+        match action(target_path, config) {
+            Err(e) if !e.requires_elevation() => return Err(e),
+            result => aggregator.file_result(0, path, result, config)?,
+        }
+
+        let stats = aggregator.finish();
+        notify_finished(path, &stats, config);
+        Ok(stats)
+    } else if path.is_dir() {
+        walk_directory(target_path, config, collect_records, action)
+    } else {
+        Err(UnblockerError::PathNotFound(target_path.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+    use crate::unblocker::unblock_file_outcome;
+    use std::fs::{self, File};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let temp_dir = tempdir().unwrap();
+        let backend = Arc::new(MemoryStreamBackend::new());
+        let mut locked = None;
+
+        for dir in 0..4 {
+            let sub = temp_dir.path().join(format!("dir{}", dir));
+            fs::create_dir(&sub).unwrap();
+            for file in 0..25 {
+                let path = sub.join(format!("file{}.txt", file));
+                File::create(&path).unwrap();
+                if file % 3 == 0 {
+                    backend.write_stream(&path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+                }
+                if dir == 2 && file == 5 {
+                    backend.deny_access(&path);
+                    locked = Some(path);
+                }
+            }
+        }
+        assert!(locked.is_some());
+
+        let run = |workers: usize| {
+            let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+            config.backend = backend.clone();
+            config.dry_run = true;
+            config.workers = workers;
+
+            let stats = walk_directory(&temp_dir.path().to_string_lossy(), &mut config, true, unblock_file_outcome).unwrap();
+            (stats, config.requires_elevation)
+        };
+
+        let (sequential, sequential_elevation) = run(1);
+        let (parallel, parallel_elevation) = run(4);
+
+        assert_eq!(parallel.files_processed, 100);
+        assert_eq!(parallel.files_unblocked, sequential.files_unblocked);
+        assert_eq!(parallel.files_no_ads, sequential.files_no_ads);
+        assert_eq!(parallel.permission_errors, 1);
+        assert!(sequential_elevation && parallel_elevation);
+
+        let paths = |stats: &UnblockStats| stats.records.iter().map(|r| r.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&parallel), paths(&sequential));
+    }
+
+    #[test]
+    fn test_cancel_stops_after_current_file() {
+        let temp_dir = tempdir().unwrap();
+        for file in 0..40 {
+            File::create(temp_dir.path().join(format!("file{}.txt", file))).unwrap();
+        }
+
+        for workers in [1, 4] {
+            let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+            config.backend = Arc::new(MemoryStreamBackend::new());
+            config.workers = workers;
+            let processed = std::sync::atomic::AtomicUsize::new(0);
+
+            let stats = walk_directory(&temp_dir.path().to_string_lossy(), &mut config, false, |path, config| {
+                if processed.fetch_add(1, Ordering::SeqCst) == 2 {
+                    config.cancel.cancel();
+                }
+                unblock_file_outcome(path, config)
+            }).unwrap();
+
+            assert_eq!(stats.cancelled, Some(CancelReason::Requested));
+            assert_eq!(stats.files_processed, processed.load(Ordering::SeqCst));
+            assert!(stats.files_processed < 40, "{} workers processed {}", workers, stats.files_processed);
+            if workers == 1 {
+                assert_eq!(stats.files_processed, 3);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parallel_stops_on_aggregation_error() {
+        let temp_dir = tempdir().unwrap();
+        for file in 0..2000 {
+            File::create(temp_dir.path().join(format!("file{}.txt", file))).unwrap();
+        }
+
+        // Every failure is logged, and a message longer than the log buffer fails to write at once
+        let mut config = Config::new(false, None, temp_dir.path().to_string_lossy().to_string()).unwrap();
+        config.log_path = Some("/dev/full".to_string());
+        config.workers = 2;
+
+        let result = walk_directory(&temp_dir.path().to_string_lossy(), &mut config, false, |path, _| {
+            Err(UnblockerError::Config(format!("cannot process {}{}", path, " ".repeat(10_000))))
+        });
+        assert!(matches!(result, Err(UnblockerError::Io(_))), "{:?}", result.map(|stats| stats.summary()));
+    }
+}
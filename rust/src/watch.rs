@@ -0,0 +1,207 @@
+//! Watch mode: unblock files as they are added to watched folders.
+
+use crate::config::Config;
+use crate::error::{Result, UnblockerError};
+use crate::filter::PathFilter;
+use crate::observer::notify_finished;
+use crate::ui::log_message;
+use crate::unblocker::{unblock_checked, FileChecks, UnblockStats};
+use crate::walker::{relative_to, Aggregator};
+use notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Default quiet period before a changed file is considered completely written
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How often the stop condition is checked while no changes arrive
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Check whether a file is left out by the filters, including through an excluded parent folder
+fn filtered(relative: &Path, filter: &PathFilter) -> bool {
+    relative.ancestors().skip(1).any(|dir| filter.prunes_dir(dir)) || !filter.allows_file(relative)
+}
+
+/// Watch directories and unblock every file created or rewritten in them
+///
+/// A file is processed once no change to it has been seen for `debounce`, so
+/// downloads and copies still in progress are left alone. Each file goes
+/// through the same checks as a normal run. Runs until `config.cancel` is
+/// cancelled or its time limit is reached, and returns the statistics of
+/// everything processed meanwhile.
+pub fn watch_targets(roots: &[String], config: &mut Config, debounce: Duration) -> Result<UnblockStats> {
+    let filter = PathFilter::new(&config.include, &config.exclude)?;
+    let checks = FileChecks::new(config)?;
+    let roots: Vec<PathBuf> = roots.iter().map(PathBuf::from).collect();
+    if let Some(root) = roots.iter().find(|root| !root.is_dir()) {
+        return Err(UnblockerError::Config(format!("watch: not a directory: {}", root.display())));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(debounce, tx)?;
+    for root in &roots {
+        debouncer.watcher().watch(root, RecursiveMode::Recursive)?;
+        log_message(&format!("Watching directory: {}", root.display()), config)?;
+        if let Some(observer) = &config.observer {
+            observer.started(root, None);
+        }
+    }
+
+    // One aggregator per root, so that each root is reported with its own statistics
+    let mut aggregators: Vec<Aggregator> = roots.iter()
+        .map(|_| Aggregator::new(config, config.collect_records))
+        .collect();
+    let mut index = 0;
+    // Unblocking can itself raise a change notification, so recently handled files are ignored for a while
+    let mut recent: HashMap<PathBuf, Instant> = HashMap::new();
+    let quiet = debounce + Duration::from_secs(1);
+
+    while !config.cancel.is_cancelled() {
+        let events = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(events)) => events,
+            Ok(Err(e)) => {
+                log_message(&format!("Watch error: {}", e), config)?;
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        recent.retain(|_, handled| handled.elapsed() < quiet);
+        // A checksum list may have been added or replaced since the last batch
+        checks.forget_sidecars();
+
+        for event in events {
+            // A file still being written to is reported again with `Any` once it has been quiet for `debounce`
+            if event.kind == DebouncedEventKind::AnyContinuous {
+                continue;
+            }
+            let path = event.path;
+            if !path.is_file() || recent.contains_key(&path) {
+                continue;
+            }
+            let root = (0..roots.len())
+                .filter(|&slot| path.starts_with(&roots[slot]))
+                .max_by_key(|&slot| roots[slot].as_os_str().len());
+            let Some(root) = root else {
+                continue;
+            };
+            if filtered(relative_to(&roots[root], &path), &filter) {
+                continue;
+            }
+
+            log_message(&format!("Change detected: {}", path.display()), config)?;
+            let result = unblock_checked(&path.to_string_lossy(), &checks, config);
+            aggregators[root].file_result(index, &path, result, config)?;
+            index += 1;
+            recent.insert(path, Instant::now());
+        }
+        config.journal.sync()?;
+    }
+
+    let cancelled = config.cancel.reason();
+    let mut stats = UnblockStats::default();
+    for (root, mut aggregator) in roots.iter().zip(aggregators) {
+        aggregator.cancelled = cancelled;
+        let root_stats = aggregator.finish();
+        notify_finished(root, &root_stats, config);
+        stats.merge(&root_stats);
+    }
+    log_message(&format!("Stopped watching. {}", stats.summary()), config)?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryStreamBackend, StreamBackend};
+    use crate::cancel::CancelReason;
+    use crate::path_utils::ZONE_IDENTIFIER_STREAM;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_watch_unblocks_new_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().to_string_lossy().to_string();
+        let drop = temp_dir.path().join("build.zip");
+        let ignored = temp_dir.path().join("notes.txt");
+
+        let backend = Arc::new(MemoryStreamBackend::new());
+        for path in [&drop, &ignored] {
+            backend.write_stream(path, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        }
+        let mut config = Config::new(false, None, root.clone()).unwrap();
+        config.backend = backend.clone();
+        config.include = vec!["*.zip".to_string()];
+
+        let cancel = config.cancel.clone();
+        let stats = std::thread::scope(|scope| {
+            let watcher = scope.spawn(|| watch_targets(&[root], &mut config, Duration::from_millis(100)));
+
+            std::thread::sleep(Duration::from_millis(200));
+            fs::write(&drop, b"first half").unwrap();
+            fs::write(&ignored, b"text").unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while backend.has_stream(&drop, ZONE_IDENTIFIER_STREAM) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            cancel.cancel();
+            watcher.join().unwrap().unwrap()
+        });
+
+        assert_eq!(stats.cancelled, Some(CancelReason::Requested));
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_processed, 1);
+        assert!(!backend.has_stream(&drop, ZONE_IDENTIFIER_STREAM));
+        assert!(backend.has_stream(&ignored, ZONE_IDENTIFIER_STREAM));
+    }
+
+    #[test]
+    fn test_watch_waits_for_continuous_writes() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().to_string_lossy().to_string();
+        let download = temp_dir.path().join("large.zip");
+        fs::write(&download, b"").unwrap();
+
+        let backend = Arc::new(MemoryStreamBackend::new());
+        backend.write_stream(&download, ZONE_IDENTIFIER_STREAM, b"[ZoneTransfer]\r\nZoneId=3\r\n").unwrap();
+        let mut config = Config::new(false, None, root.clone()).unwrap();
+        config.backend = backend.clone();
+
+        let cancel = config.cancel.clone();
+        let mut unblocked_while_writing = false;
+        let stats = std::thread::scope(|scope| {
+            let watcher = scope.spawn(|| watch_targets(&[root], &mut config, Duration::from_millis(100)));
+
+            std::thread::sleep(Duration::from_millis(200));
+            // Keep writing for several debounce periods, as a slow download would
+            let mut file = fs::OpenOptions::new().append(true).open(&download).unwrap();
+            let writing_until = Instant::now() + Duration::from_millis(800);
+            while Instant::now() < writing_until {
+                file.write_all(b"chunk").unwrap();
+                std::thread::sleep(Duration::from_millis(20));
+                unblocked_while_writing |= !backend.has_stream(&download, ZONE_IDENTIFIER_STREAM);
+            }
+            drop(file);
+
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while backend.has_stream(&download, ZONE_IDENTIFIER_STREAM) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            cancel.cancel();
+            watcher.join().unwrap().unwrap()
+        });
+
+        assert!(!unblocked_while_writing);
+        assert_eq!(stats.files_unblocked, 1);
+        assert_eq!(stats.files_processed, 1);
+        assert!(!backend.has_stream(&download, ZONE_IDENTIFIER_STREAM));
+    }
+}
@@ -0,0 +1,421 @@
+//! Parsing and serialization of Zone.Identifier stream contents.
+
+use crate::error::{Result, UnblockerError};
+use std::fmt;
+use std::str::FromStr;
+
+/// Windows-1252 code points for the bytes 0x80-0x9F (the rest match Latin-1)
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+
+/// URL security zone recorded in a Zone.Identifier stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Zone {
+    LocalMachine,
+    Intranet,
+    Trusted,
+    Internet,
+    Restricted,
+    Other(u32),
+}
+
+impl Zone {
+    /// Map a numeric ZoneId to a zone
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            0 => Zone::LocalMachine,
+            1 => Zone::Intranet,
+            2 => Zone::Trusted,
+            3 => Zone::Internet,
+            4 => Zone::Restricted,
+            other => Zone::Other(other),
+        }
+    }
+
+    /// Numeric ZoneId as written to the stream
+    pub fn id(&self) -> u32 {
+        match self {
+            Zone::LocalMachine => 0,
+            Zone::Intranet => 1,
+            Zone::Trusted => 2,
+            Zone::Internet => 3,
+            Zone::Restricted => 4,
+            Zone::Other(id) => *id,
+        }
+    }
+
+    /// Human readable zone name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Zone::LocalMachine => "Local Machine",
+            Zone::Intranet => "Local Intranet",
+            Zone::Trusted => "Trusted Sites",
+            Zone::Internet => "Internet",
+            Zone::Restricted => "Restricted Sites",
+            Zone::Other(_) => "Unknown",
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.id(), self.name())
+    }
+}
+
+impl FromStr for Zone {
+    type Err = UnblockerError;
+
+    /// Accept either a numeric ZoneId or a zone name such as `internet`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(id) = s.parse::<u32>() {
+            return Ok(Zone::from_id(id));
+        }
+
+        match s.to_ascii_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "localmachine" | "local" | "computer" => Ok(Zone::LocalMachine),
+            "intranet" | "localintranet" => Ok(Zone::Intranet),
+            "trusted" | "trustedsites" => Ok(Zone::Trusted),
+            "internet" => Ok(Zone::Internet),
+            "restricted" | "restrictedsites" | "untrusted" => Ok(Zone::Restricted),
+            _ => Err(UnblockerError::InvalidZoneIdentifier(format!("Unknown zone: {}", s))),
+        }
+    }
+}
+
+/// Text encoding a Zone.Identifier stream was stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZoneEncoding {
+    /// UTF-8 without a byte order mark (also covers plain ASCII)
+    #[default]
+    Utf8,
+    /// UTF-8 with a byte order mark
+    Utf8Bom,
+    /// Windows-1252 ANSI code page
+    Ansi,
+    /// UTF-16 little endian with a byte order mark
+    Utf16Le,
+}
+
+/// Parsed contents of a Zone.Identifier stream
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ZoneInfo {
+    /// Security zone the file came from
+    pub zone_id: Option<Zone>,
+    /// Page the download was started from
+    pub referrer_url: Option<String>,
+    /// URL the file itself was downloaded from
+    pub host_url: Option<String>,
+    /// Zone assigned by the application that saved the file
+    pub app_zone_id: Option<String>,
+    /// Package family name of the packaged app that last wrote the file
+    pub last_writer_package_family_name: Option<String>,
+    /// Keys in the `[ZoneTransfer]` section that are not otherwise understood, in file order
+    pub extra: Vec<(String, String)>,
+    /// Encoding used when the stream is serialized
+    pub encoding: ZoneEncoding,
+}
+
+impl ZoneInfo {
+    /// Create stream contents for the given zone
+    pub fn new(zone: Zone) -> Self {
+        Self {
+            zone_id: Some(zone),
+            ..Self::default()
+        }
+    }
+
+    /// Parse raw Zone.Identifier stream bytes
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let (text, encoding) = decode(bytes)?;
+        let mut info = ZoneInfo {
+            encoding,
+            ..Self::default()
+        };
+        let mut in_zone_transfer = false;
+        let mut found_section = false;
+
+        for line in text.trim_end_matches('\0').lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_zone_transfer = section.trim().eq_ignore_ascii_case("ZoneTransfer");
+                found_section |= in_zone_transfer;
+                continue;
+            }
+
+            if !in_zone_transfer {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "zoneid" => {
+                    let id = value.parse::<u32>().map_err(|_| {
+                        UnblockerError::InvalidZoneIdentifier(format!("Invalid ZoneId: {}", value))
+                    })?;
+                    info.zone_id = Some(Zone::from_id(id));
+                }
+                "referrerurl" => info.referrer_url = Some(value.to_string()),
+                "hosturl" => info.host_url = Some(value.to_string()),
+                "appzoneid" => info.app_zone_id = Some(value.to_string()),
+                "lastwriterpackagefamilyname" => {
+                    info.last_writer_package_family_name = Some(value.to_string())
+                }
+                _ => info.extra.push((key.to_string(), value.to_string())),
+            }
+        }
+
+        if !found_section {
+            return Err(UnblockerError::InvalidZoneIdentifier(
+                "Missing [ZoneTransfer] section".to_string()
+            ));
+        }
+
+        Ok(info)
+    }
+
+    /// Serialize back to stream bytes in the recorded encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(&self.to_string(), self.encoding)
+    }
+}
+
+/// Set the ZoneId of a stream that could not be parsed, keeping every other line as it is
+///
+/// Each `ZoneId=` line of the `[ZoneTransfer]` section is replaced. Without
+/// one, the line is added below the section header, and the header is added
+/// first if it is missing. A stream that cannot even be decoded is replaced by
+/// a minimal one for the zone.
+pub fn rewrite_zone_id(bytes: &[u8], zone: Zone) -> Vec<u8> {
+    let Ok((text, encoding)) = decode(bytes) else {
+        return ZoneInfo::new(zone).to_bytes();
+    };
+    let zone_line = format!("ZoneId={}", zone.id());
+    let mut rewritten = String::with_capacity(text.len() + zone_line.len() + 2);
+    let mut header_end = None;
+    let mut newline = "\r\n";
+    let mut replaced = false;
+    let mut in_zone_transfer = false;
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim();
+        if let Some(section) = content.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_zone_transfer = section.trim().eq_ignore_ascii_case("ZoneTransfer");
+            rewritten.push_str(line);
+            if in_zone_transfer && header_end.is_none() {
+                match &line[line.trim_end().len()..] {
+                    "" => rewritten.push_str(newline),
+                    ending => newline = if ending.ends_with("\r\n") { "\r\n" } else { "\n" },
+                }
+                header_end = Some(rewritten.len());
+            }
+            continue;
+        }
+
+        let key = content.split_once('=').map(|(key, _)| key.trim());
+        if in_zone_transfer && key.is_some_and(|key| key.eq_ignore_ascii_case("ZoneId")) {
+            // Keep the line ending the stream used
+            let ending = &line[line.trim_end().len()..];
+            rewritten.push_str(&zone_line);
+            rewritten.push_str(ending);
+            replaced = true;
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+
+    if !replaced {
+        match header_end {
+            Some(end) => rewritten.insert_str(end, &format!("{}{}", zone_line, newline)),
+            None => rewritten.insert_str(0, &format!("[ZoneTransfer]\r\n{}\r\n", zone_line)),
+        }
+    }
+
+    encode(&rewritten, encoding)
+}
+
+impl fmt::Display for ZoneInfo {
+    /// Render the `[ZoneTransfer]` section with CRLF line endings, as Windows writes it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[ZoneTransfer]\r\n")?;
+        if let Some(zone) = self.zone_id {
+            write!(f, "ZoneId={}\r\n", zone.id())?;
+        }
+        if let Some(name) = &self.last_writer_package_family_name {
+            write!(f, "LastWriterPackageFamilyName={}\r\n", name)?;
+        }
+        if let Some(app_zone) = &self.app_zone_id {
+            write!(f, "AppZoneId={}\r\n", app_zone)?;
+        }
+        if let Some(referrer) = &self.referrer_url {
+            write!(f, "ReferrerUrl={}\r\n", referrer)?;
+        }
+        if let Some(host) = &self.host_url {
+            write!(f, "HostUrl={}\r\n", host)?;
+        }
+        for (key, value) in &self.extra {
+            write!(f, "{}={}\r\n", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode stream bytes, detecting the encoding from the BOM or the content
+fn decode(bytes: &[u8]) -> Result<(String, ZoneEncoding)> {
+    if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        if rest.len() % 2 != 0 {
+            return Err(UnblockerError::InvalidZoneIdentifier(
+                "Truncated UTF-16 stream".to_string()
+            ));
+        }
+        let units: Vec<u16> = rest.chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let text = String::from_utf16(&units).map_err(|_| {
+            UnblockerError::InvalidZoneIdentifier("Invalid UTF-16 stream".to_string())
+        })?;
+        return Ok((text, ZoneEncoding::Utf16Le));
+    }
+
+    if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        let text = std::str::from_utf8(rest).map_err(|_| {
+            UnblockerError::InvalidZoneIdentifier("Invalid UTF-8 stream".to_string())
+        })?;
+        return Ok((text.to_string(), ZoneEncoding::Utf8Bom));
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok((text.to_string(), ZoneEncoding::Utf8)),
+        Err(_) => Ok((bytes.iter().map(|&b| decode_cp1252(b)).collect(), ZoneEncoding::Ansi)),
+    }
+}
+
+fn encode(text: &str, encoding: ZoneEncoding) -> Vec<u8> {
+    match encoding {
+        ZoneEncoding::Utf8 => text.as_bytes().to_vec(),
+        ZoneEncoding::Utf8Bom => UTF8_BOM.iter().chain(text.as_bytes()).copied().collect(),
+        ZoneEncoding::Ansi => text.chars().map(encode_cp1252).collect(),
+        ZoneEncoding::Utf16Le => UTF16LE_BOM.iter().copied()
+            .chain(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()))
+            .collect(),
+    }
+}
+
+fn decode_cp1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => CP1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+fn encode_cp1252(c: char) -> u8 {
+    match c as u32 {
+        code @ (0x00..=0x7F | 0xA0..=0xFF) => code as u8,
+        _ => CP1252_HIGH.iter()
+            .position(|&high| high == c)
+            .map(|index| 0x80 + index as u8)
+            .unwrap_or(b'?'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDGE_STREAM: &str = "[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://example.com/downloads\r\nHostUrl=https://cdn.example.com/setup.exe\r\n";
+
+    #[test]
+    fn test_parse_utf8() {
+        let info = ZoneInfo::parse(EDGE_STREAM.as_bytes()).unwrap();
+        assert_eq!(info.zone_id, Some(Zone::Internet));
+        assert_eq!(info.referrer_url.as_deref(), Some("https://example.com/downloads"));
+        assert_eq!(info.host_url.as_deref(), Some("https://cdn.example.com/setup.exe"));
+        assert_eq!(info.encoding, ZoneEncoding::Utf8);
+        assert_eq!(info.to_bytes(), EDGE_STREAM.as_bytes());
+    }
+
+    #[test]
+    fn test_parse_utf16le_round_trip() {
+        let mut bytes = UTF16LE_BOM.to_vec();
+        bytes.extend(EDGE_STREAM.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+
+        let info = ZoneInfo::parse(&bytes).unwrap();
+        assert_eq!(info.encoding, ZoneEncoding::Utf16Le);
+        assert_eq!(info.zone_id, Some(Zone::Internet));
+        assert_eq!(info.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_parse_ansi_round_trip() {
+        let bytes = b"[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/caf\xe9\x80.zip\r\n".to_vec();
+
+        let info = ZoneInfo::parse(&bytes).unwrap();
+        assert_eq!(info.encoding, ZoneEncoding::Ansi);
+        assert_eq!(info.host_url.as_deref(), Some("https://example.com/caf\u{e9}\u{20ac}.zip"));
+        assert_eq!(info.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_parse_keeps_unknown_keys() {
+        let text = "[ZoneTransfer]\nZoneId=2\nAppZoneId=4\nLastWriterPackageFamilyName=Microsoft.Edge_8wekyb3d8bbwe\nCustomKey=value\n";
+        let info = ZoneInfo::parse(text.as_bytes()).unwrap();
+
+        assert_eq!(info.zone_id, Some(Zone::Trusted));
+        assert_eq!(info.app_zone_id.as_deref(), Some("4"));
+        assert_eq!(info.last_writer_package_family_name.as_deref(), Some("Microsoft.Edge_8wekyb3d8bbwe"));
+        assert_eq!(info.extra, vec![("CustomKey".to_string(), "value".to_string())]);
+        assert_eq!(ZoneInfo::parse(&info.to_bytes()).unwrap(), info);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(ZoneInfo::parse(b"").is_err());
+        assert!(ZoneInfo::parse(b"ZoneId=3\r\n").is_err());
+        assert!(ZoneInfo::parse(b"[ZoneTransfer]\r\nZoneId=abc\r\n").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_zone_id() {
+        let invalid = "[ZoneTransfer]\r\nZoneId=abc\r\nHostUrl=https://example.com/a.zip\r\n";
+        let rewritten = rewrite_zone_id(invalid.as_bytes(), Zone::Intranet);
+        assert_eq!(rewritten, b"[ZoneTransfer]\r\nZoneId=1\r\nHostUrl=https://example.com/a.zip\r\n");
+
+        let no_zone = rewrite_zone_id(b"[ZoneTransfer]\nReferrerUrl=https://example.com/", Zone::Trusted);
+        assert_eq!(no_zone, b"[ZoneTransfer]\nZoneId=2\nReferrerUrl=https://example.com/");
+
+        let no_section = rewrite_zone_id(b"HostUrl=about:internet\r\n", Zone::Intranet);
+        assert_eq!(no_section, b"[ZoneTransfer]\r\nZoneId=1\r\nHostUrl=about:internet\r\n");
+
+        let mut utf16 = UTF16LE_BOM.to_vec();
+        utf16.extend("[ZoneTransfer]\r\nZoneId=x\r\n".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        let rewritten = rewrite_zone_id(&utf16, Zone::Intranet);
+        assert_eq!(decode(&rewritten).unwrap(), ("[ZoneTransfer]\r\nZoneId=1\r\n".to_string(), ZoneEncoding::Utf16Le));
+
+        assert_eq!(rewrite_zone_id(&[0xFF, 0xFE, 0x41], Zone::Intranet), ZoneInfo::new(Zone::Intranet).to_bytes());
+    }
+
+    #[test]
+    fn test_zone_from_str() {
+        assert_eq!("3".parse::<Zone>().unwrap(), Zone::Internet);
+        assert_eq!("restricted".parse::<Zone>().unwrap(), Zone::Restricted);
+        assert_eq!("Local-Intranet".parse::<Zone>().unwrap(), Zone::Intranet);
+        assert_eq!("7".parse::<Zone>().unwrap(), Zone::Other(7));
+        assert!("somewhere".parse::<Zone>().is_err());
+    }
+}